@@ -0,0 +1,472 @@
+//! A little assembly language that compiles down to `addx` and `noop`, and back again
+//!
+//! Besides the plain instructions, a source line can be
+//!
+//! - `name:` a label, remembering the cycle the next instruction starts on
+//! - `set <v>` to make X equal `v`, knowing what it is at that point
+//! - `wait <n>` for `n` cycles of `noop`
+//! - `pixels <pattern>` to draw `#` and `.` from wherever the beam has got to
+//! - `draw_letter <letter> <row>` for one row of a letter and the gap after it
+//! - `.macro <name> <params>...` up to `.endm` to define a macro, used as
+//!   `<name> <args>...` with each `\param` in its body replaced
+//!
+//! and anything after a `;` is a comment.
+use crate::cpu::{Cpu, Instruction, Registers};
+use crate::ocr::{glyph, GLYPH_HEIGHT, GLYPH_WIDTH};
+use anyhow::{bail, ensure, Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+/// How deep macros may call other macros
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assembly {
+    pub program: Vec<Instruction>,
+    /// The cycle (from 1) each label's next instruction starts on
+    pub labels: BTreeMap<String, usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Macro {
+    params: Vec<String>,
+    body: Vec<String>,
+}
+
+/// Keeps track of where the CPU will be as the program grows, which is all
+/// `set` and `pixels` need since nothing ever jumps
+#[derive(Debug, Clone)]
+pub struct Assembler {
+    /// Of the screen being drawn on
+    pub width: usize,
+    regs: Registers,
+    /// Cycles the program takes so far
+    cycles: usize,
+    program: Vec<Instruction>,
+    /// Pixels still to draw: planned all at once, since how one stretch ends
+    /// decides what the next can start with
+    pending: Vec<bool>,
+    labels: BTreeMap<String, usize>,
+    macros: HashMap<String, Macro>,
+}
+
+impl Assembler {
+    pub fn new(width: usize) -> Self {
+        Assembler {
+            width,
+            regs: Registers::default(),
+            cycles: 0,
+            program: vec![],
+            pending: vec![],
+            labels: BTreeMap::new(),
+            macros: HashMap::new(),
+        }
+    }
+
+    pub fn emit(&mut self, i: Instruction) {
+        self.flush();
+        self.push(i);
+    }
+
+    fn push(&mut self, i: Instruction) {
+        i.execute(&mut self.regs);
+        self.cycles += i.cycles();
+        self.program.push(i);
+    }
+
+    /// Draw `pattern` (lit or not) from the next cycle on, exactly one pixel per cycle
+    pub fn pixels(&mut self, pattern: &[bool]) -> Result<()> {
+        let start = self.cycles + self.pending.len() + 1;
+        self.pending.extend_from_slice(pattern);
+        if draw(self.cycles, self.regs.x, &self.pending, self.width).is_none() {
+            self.pending.truncate(self.pending.len() - pattern.len());
+            bail!(
+                "Can't draw {} from cycle {start}",
+                pattern
+                    .iter()
+                    .map(|p| if *p { '#' } else { '.' })
+                    .collect::<String>()
+            );
+        }
+        Ok(())
+    }
+
+    /// Turn any pending pixels into instructions
+    fn flush(&mut self) {
+        let pending = std::mem::take(&mut self.pending);
+        // pixels() has already made sure this can be done
+        for i in draw(self.cycles, self.regs.x, &pending, self.width).unwrap_or_default() {
+            self.push(i);
+        }
+    }
+
+    /// Assemble some source, adding to what's already there
+    pub fn source(&mut self, source: &str) -> Result<()> {
+        let lines: Vec<String> = source.lines().map(String::from).collect();
+        self.block(&lines, 0)
+    }
+
+    fn block(&mut self, lines: &[String], depth: usize) -> Result<()> {
+        ensure!(
+            depth <= MAX_DEPTH,
+            "Macros nested more than {MAX_DEPTH} deep"
+        );
+        let mut lines = lines.iter().enumerate();
+        while let Some((n, line)) = lines.next() {
+            let context = || format!("Line {}: {line:?}", n + 1);
+            let mut text = line.split(';').next().unwrap_or_default().trim();
+            if let Some((label, rest)) = text.split_once(':') {
+                self.label(label.trim()).with_context(context)?;
+                text = rest.trim();
+            }
+            let words: Vec<&str> = text.split_whitespace().collect();
+            match words.as_slice() {
+                [] => (),
+                [".macro", name, params @ ..] => {
+                    let mut body = vec![];
+                    loop {
+                        match lines.next() {
+                            Some((_, l))
+                                if l.split(';').next().unwrap_or_default().trim() == ".endm" =>
+                            {
+                                break
+                            }
+                            Some((_, l)) => body.push(l.clone()),
+                            None => bail!("Line {}: .macro {name} has no .endm", n + 1),
+                        }
+                    }
+                    let params = params.iter().map(|p| String::from(*p)).collect();
+                    self.macros
+                        .insert(String::from(*name), Macro { params, body });
+                }
+                _ => self.statement(&words, depth).with_context(context)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Remember where the next instruction starts
+    fn label(&mut self, label: &str) -> Result<()> {
+        ensure!(
+            !label.is_empty() && !label.contains(char::is_whitespace),
+            "Bad label {label:?}"
+        );
+        let cycle = self.cycles + self.pending.len() + 1;
+        ensure!(
+            self.labels.insert(String::from(label), cycle).is_none(),
+            "Label {label:?} defined twice"
+        );
+        Ok(())
+    }
+
+    fn statement(&mut self, words: &[&str], depth: usize) -> Result<()> {
+        let number = |w: &str| -> Result<isize> {
+            w.parse().with_context(|| format!("{w:?} is not a number"))
+        };
+        match words {
+            ["set", v] => {
+                let v = number(v)?;
+                self.flush();
+                if v != self.regs.x {
+                    self.emit(Instruction::Addx(v - self.regs.x));
+                }
+            }
+            ["wait", n] => {
+                let n = number(n)?;
+                ensure!(n >= 0, "Can't wait {n} cycles");
+                for _ in 0..n {
+                    self.emit(Instruction::Noop);
+                }
+            }
+            ["pixels", pattern] => {
+                let pattern = pattern
+                    .chars()
+                    .map(|c| match c {
+                        '#' => Ok(true),
+                        '.' => Ok(false),
+                        _ => bail!("{c:?} isn't a pixel; use # or ."),
+                    })
+                    .collect::<Result<Vec<bool>>>()?;
+                self.pixels(&pattern)?;
+            }
+            ["draw_letter", letter, row] => {
+                let mut chars = letter.chars();
+                let (Some(c), None) = (chars.next(), chars.next()) else {
+                    bail!("{letter:?} isn't a single letter");
+                };
+                let g = glyph(c).with_context(|| format!("No glyph for {c:?}"))?;
+                let row = number(row)? as usize;
+                ensure!(
+                    row < GLYPH_HEIGHT,
+                    "Letters only have rows 0 to {}",
+                    GLYPH_HEIGHT - 1
+                );
+                let mut pattern = g[row * GLYPH_WIDTH..(row + 1) * GLYPH_WIDTH].to_vec();
+                pattern.push(false);
+                self.pixels(&pattern)?;
+            }
+            [name, args @ ..] if self.macros.contains_key(*name) => {
+                let m = self.macros[*name].clone();
+                ensure!(
+                    args.len() == m.params.len(),
+                    "{name} takes {} arguments, not {}",
+                    m.params.len(),
+                    args.len()
+                );
+                let body: Vec<String> = m
+                    .body
+                    .iter()
+                    .map(|l| {
+                        m.params
+                            .iter()
+                            .zip(args)
+                            .fold(l.clone(), |l, (p, a)| l.replace(&format!("\\{p}"), a))
+                    })
+                    .collect();
+                self.block(&body, depth + 1)
+                    .with_context(|| format!("In macro {name}"))?;
+            }
+            _ => self.emit(Instruction::parse(&words.join(" "))?),
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Assembly {
+        self.flush();
+        Assembly {
+            program: self.program,
+            labels: self.labels,
+        }
+    }
+}
+
+/// Assemble for the usual 40 column screen
+pub fn assemble(source: &str) -> Result<Assembly> {
+    let mut asm = Assembler::new(40);
+    asm.source(source)?;
+    Ok(asm.finish())
+}
+
+/// Instructions that, starting on the cycle after `done` with X at `x`, draw `pattern` exactly
+///
+/// X only changes at the end of an `addx`, so which pixels can follow which is
+/// limited; works backwards through the pattern to find the fewest instructions.
+pub fn draw(done: usize, x: isize, pattern: &[bool], width: usize) -> Option<Vec<Instruction>> {
+    let n = pattern.len();
+    let width = width.max(1);
+    let fits = |i: usize, x: isize| {
+        let col = ((done + i) % width) as isize;
+        (x - 1 <= col && x + 1 >= col) == pattern[i]
+    };
+    // Anything from one left of the screen to one right of it will do for X:
+    // further out is just as dark as the edges
+    let xs: Vec<isize> = (-2..=width as isize + 1).collect();
+    let index = |x: isize| (x.clamp(-2, width as isize + 1) + 2) as usize;
+
+    // best[i][k]: fewest instructions to draw from pixel i on, with X at xs[k]
+    let mut best = vec![vec![None; xs.len()]; n + 1];
+    best[n] = vec![Some(0usize); xs.len()];
+    // cheapest X to arrive with at each pixel, after an addx
+    let mut landing: Vec<Option<(usize, usize)>> = vec![None; n + 1];
+    // what it takes to draw pixel i on, and where X should go next if anywhere
+    let cost =
+        |best: &[Vec<Option<usize>>], landing: &[Option<(usize, usize)>], i: usize, x: isize| {
+            if !fits(i, x) {
+                return None;
+            }
+            let stay = best[i + 1][index(x)].map(|c| (c + 1, None));
+            let jump = if i + 2 == n {
+                // an addx to finish on leaves X alone, ready for whatever comes next
+                fits(i + 1, x).then_some((1, Some(x)))
+            } else {
+                (i + 2 < n && fits(i + 1, x))
+                    .then(|| landing[i + 2])
+                    .flatten()
+                    .map(|(c, to)| (c + 1, Some(xs[to])))
+            };
+            match (stay, jump) {
+                (Some(s), Some(j)) if j.0 < s.0 => Some(j),
+                (Some(s), _) => Some(s),
+                (None, j) => j,
+            }
+        };
+    for i in (0..n).rev() {
+        for (k, &x) in xs.iter().enumerate() {
+            best[i][k] = cost(&best, &landing, i, x).map(|(c, _)| c);
+        }
+        landing[i] = best[i]
+            .iter()
+            .enumerate()
+            .filter_map(|(k, c)| c.map(|c| (c, k)))
+            .min();
+    }
+
+    // Then forwards, taking the choices that got the best counts
+    let mut out = vec![];
+    let (mut i, mut x) = (0, x);
+    while i < n {
+        match cost(&best, &landing, i, x)? {
+            (_, None) => {
+                out.push(Instruction::Noop);
+                i += 1;
+            }
+            (_, Some(to)) => {
+                out.push(Instruction::Addx(to - x));
+                x = to;
+                i += 2;
+            }
+        }
+    }
+    Some(out)
+}
+
+/// The program as assembly, each instruction noting its cycles, X during them and what it draws
+///
+/// Everything added is a comment, so it assembles straight back.
+pub fn disassemble(program: &[Instruction], width: usize) -> String {
+    let width = width.max(1);
+    let mut during: Vec<(usize, isize)> = vec![];
+    Cpu::new(program.to_vec()).run(&mut |c: &Cpu| during.push((c.cycle(), c.regs.x)));
+
+    let mut out = String::new();
+    let mut cycles = during.into_iter();
+    for i in program {
+        let mine: Vec<(usize, isize)> = cycles.by_ref().take(i.cycles()).collect();
+        let (first, x) = mine[0];
+        let last = mine[mine.len() - 1].0;
+        let span = if first == last {
+            format!("cycle {first}")
+        } else {
+            format!("cycles {first}-{last}")
+        };
+        let drawn: String = mine
+            .iter()
+            .map(|(c, x)| {
+                let col = ((c - 1) % width) as isize;
+                if x - 1 <= col && x + 1 >= col {
+                    '#'
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        let _ = writeln!(out, "{:<12}; {span}, X = {x}, draws {drawn}", i.to_string());
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::parse_program;
+    use crate::crt::Crt;
+    use crate::ocr;
+
+    fn screen(program: &[Instruction]) -> Crt {
+        let mut crt = Crt::new(40, 6);
+        Cpu::new(program.to_vec()).run(&mut crt);
+        crt
+    }
+
+    #[test]
+    fn letters() -> Result<()> {
+        let source = "
+            ; a whole line of text, one row at a time
+            .macro row r
+            draw_letter F \\r
+            draw_letter Z \\r
+            draw_letter B \\r
+            draw_letter P \\r
+            draw_letter B \\r
+            draw_letter F \\r
+            draw_letter Z \\r
+            draw_letter F \\r ; last one
+            .endm
+            top: row 0
+            row 1
+            row 2
+            middle:
+            row 3
+            row 4
+            row 5
+        ";
+        let asm = assemble(source)?;
+        assert_eq!(asm.labels["top"], 1);
+        assert_eq!(asm.labels["middle"], 121);
+        let cycles: usize = asm.program.iter().map(|i| i.cycles()).sum();
+        assert_eq!(cycles, 240);
+        assert_eq!(ocr::read(&screen(&asm.program))?, "FZBPBFZF");
+        Ok(())
+    }
+
+    #[test]
+    fn directives() -> Result<()> {
+        let asm = assemble("set 1\nset 5 ; +4\nwait 2\nlater: addx -3 ; plain\nnoop")?;
+        assert_eq!(
+            asm.program,
+            parse_program("addx 4\nnoop\nnoop\naddx -3\nnoop")?
+        );
+        assert_eq!(asm.labels["later"], 5);
+
+        for bad in [
+            "pixels #x#",
+            "draw_letter Q 0",
+            "draw_letter F 6",
+            "x: noop\nx: noop",
+            ".macro m a\nnoop",
+            ".macro m\nm\n.endm\nm",
+            ".macro m a\n.endm\nm",
+            "jump 3",
+            "wait -1",
+        ] {
+            assert!(assemble(bad).is_err(), "{bad:?}");
+        }
+        for (bad, line) in [("noop\nx y: noop", 2), ("x: noop\n\nx: noop", 3)] {
+            let e = format!("{:#}", assemble(bad).unwrap_err());
+            assert!(e.starts_with(&format!("Line {line}: ")), "{e}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn any_pattern() -> Result<()> {
+        // every pattern of 10 pixels, from a few starting points
+        for bits in 0..1 << 10 {
+            let pattern: Vec<bool> = (0..10).map(|b| bits >> b & 1 == 1).collect();
+            for (done, x) in [(0, 1), (17, 30), (35, -40)] {
+                let Some(plan) = draw(done, x, &pattern, 40) else {
+                    continue;
+                };
+                let mut lit = vec![];
+                let mut last_x = x;
+                let mut cpu = Cpu::new(plan);
+                cpu.regs.x = x;
+                cpu.run(&mut |c: &Cpu| {
+                    let col = ((done + c.cycle() - 1) % 40) as isize;
+                    lit.push(c.regs.x - 1 <= col && c.regs.x + 1 >= col);
+                    last_x = c.regs.x;
+                });
+                assert_eq!(lit, pattern);
+                // X ends up where the last pixel had it, not somewhere arbitrary
+                assert_eq!(cpu.regs.x, last_x);
+            }
+        }
+        // X can't move before the end of the first cycle's instruction
+        assert!(draw(0, 5, &[false, true], 40).is_none());
+        assert_eq!(
+            draw(0, 1, &[true, true], 40),
+            Some(vec![Instruction::Addx(0)])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn round_trip() -> Result<()> {
+        let program = parse_program(include_str!("../sample.txt"))?;
+        let listing = disassemble(&program, 40);
+        assert!(listing.starts_with("addx 15     ; cycles 1-2, X = 1, draws ##\n"));
+        assert!(listing.contains("; cycle 240, X = 17, draws .\n"));
+        assert_eq!(assemble(&listing)?.program, program);
+        Ok(())
+    }
+}
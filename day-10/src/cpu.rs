@@ -0,0 +1,228 @@
+//! The handheld's CPU, one clock cycle at a time
+use anyhow::{bail, Context, Result};
+use std::fmt;
+
+/// Everything an instruction can change
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Registers {
+    pub x: isize,
+}
+
+impl Default for Registers {
+    /// X starts at 1
+    fn default() -> Self {
+        Registers { x: 1 }
+    }
+}
+
+/// A decoded instruction
+///
+/// To add an opcode, add a variant here and give it a mnemonic in
+/// [`Instruction::parse`], a length in [`Instruction::cycles`] and an effect
+/// in [`Instruction::execute`]; the CPU and everything hooked onto it
+/// will cope without changes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    /// Does nothing for a cycle
+    Noop,
+    /// Adds its operand (which may be negative) to X at the end of its second cycle
+    Addx(isize),
+}
+
+impl Instruction {
+    pub fn parse(line: &str) -> Result<Self> {
+        let mut words = line.split_whitespace();
+        let op = words.next().unwrap_or_default();
+        let mut operand = || -> Result<isize> {
+            let word = words
+                .next()
+                .with_context(|| format!("{op} needs an operand"))?;
+            word.parse()
+                .with_context(|| format!("{word:?} is not a valid operand for {op}"))
+        };
+        let instruction = match op {
+            "noop" => Instruction::Noop,
+            "addx" => Instruction::Addx(operand()?),
+            _ => bail!("Unknown instruction {op:?}"),
+        };
+        if let Some(extra) = words.next() {
+            bail!("Unexpected {extra:?} after {op}");
+        }
+        Ok(instruction)
+    }
+
+    /// How many cycles it takes to complete
+    pub fn cycles(&self) -> usize {
+        match self {
+            Instruction::Noop => 1,
+            Instruction::Addx(_) => 2,
+        }
+    }
+
+    /// Its effect, which lands once all its cycles are over
+    pub fn execute(&self, regs: &mut Registers) {
+        match self {
+            Instruction::Noop => (),
+            Instruction::Addx(v) => regs.x += v,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Noop => write!(f, "noop"),
+            Instruction::Addx(v) => write!(f, "addx {v}"),
+        }
+    }
+}
+
+/// One instruction per line; blank lines are skipped
+pub fn parse_program(input: &str) -> Result<Vec<Instruction>> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(i, l)| Instruction::parse(l).with_context(|| format!("Line {}: {l:?}", i + 1)))
+        .collect()
+}
+
+/// Something that wants to know what's going on during every cycle
+///
+/// Called in the middle of each cycle, once it has started but before any
+/// instruction finishing at its end has taken effect.
+pub trait Hook {
+    fn cycle(&mut self, cpu: &Cpu);
+}
+
+impl<F: FnMut(&Cpu)> Hook for F {
+    fn cycle(&mut self, cpu: &Cpu) {
+        self(cpu)
+    }
+}
+
+/// Two hooks at once; nest them for more
+impl<A: Hook, B: Hook> Hook for (A, B) {
+    fn cycle(&mut self, cpu: &Cpu) {
+        self.0.cycle(cpu);
+        self.1.cycle(cpu);
+    }
+}
+
+/// For when there's nothing to watch
+impl Hook for () {
+    fn cycle(&mut self, _: &Cpu) {}
+}
+
+/// Traces the value of X "during" each cycle
+/// Two instructions, one register
+///     addx p
+///     noop
+/// `addx p` adds p (integer, which may be negative) to register X;
+/// it takes *two* cycles to complete: X does not change until one cycle later
+/// (this doesn't mean data race hell, it means it takes two cycles)
+/// X changes "after" the cycle rather than "during"
+/// `noop` does nothing and takes one cycle to complete
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cpu {
+    pub regs: Registers,
+    program: Vec<Instruction>,
+    /// The instruction being carried out
+    pc: usize,
+    /// Cycles already spent on it
+    elapsed: usize,
+    /// The cycle under way, counting from 1; 0 before the first
+    cycle: usize,
+}
+
+impl Cpu {
+    pub fn new(program: Vec<Instruction>) -> Self {
+        Cpu {
+            regs: Registers::default(),
+            program,
+            pc: 0,
+            elapsed: 0,
+            cycle: 0,
+        }
+    }
+
+    pub fn cycle(&self) -> usize {
+        self.cycle
+    }
+
+    /// Which instruction is being carried out, counting from 0
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// How many cycles of the current instruction are already over
+    pub fn elapsed(&self) -> usize {
+        self.elapsed
+    }
+
+    /// What's being carried out, if the program hasn't run out
+    pub fn current(&self) -> Option<Instruction> {
+        self.program.get(self.pc).copied()
+    }
+
+    pub fn halted(&self) -> bool {
+        self.pc >= self.program.len()
+    }
+
+    /// Run a single clock cycle, telling the hook about it; false once the program's over
+    pub fn step(&mut self, hook: &mut impl Hook) -> bool {
+        let Some(instruction) = self.current() else {
+            return false;
+        };
+        self.cycle += 1;
+        hook.cycle(self);
+        self.elapsed += 1;
+        if self.elapsed == instruction.cycles() {
+            instruction.execute(&mut self.regs);
+            self.pc += 1;
+            self.elapsed = 0;
+        }
+        true
+    }
+
+    /// Step to the end of the program
+    pub fn run(&mut self, hook: &mut impl Hook) {
+        while !self.halted() {
+            self.step(hook);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn small_program() -> Result<()> {
+        let mut cpu = Cpu::new(parse_program("noop\naddx 3\naddx -5\n")?);
+        let mut during = vec![];
+        cpu.run(&mut |c: &Cpu| during.push((c.cycle(), c.regs.x)));
+        assert_eq!(during, vec![(1, 1), (2, 1), (3, 1), (4, 4), (5, 4)]);
+        assert_eq!(cpu.regs.x, -1);
+        assert!(cpu.halted());
+        assert!(!cpu.step(&mut ()));
+        Ok(())
+    }
+
+    #[test]
+    fn bad_programs() {
+        for (program, message) in [
+            ("noop\naddx", "Line 2"),
+            ("addx 1\n\naddx one", "Line 3"),
+            ("jmp 4", "Line 1"),
+            ("noop 4", "Line 1"),
+        ] {
+            let e = parse_program(program).unwrap_err();
+            assert!(e.to_string().starts_with(message), "{program:?}: {e}");
+        }
+        assert_eq!(
+            format!("{:#}", Instruction::parse("addx one").unwrap_err()),
+            "\"one\" is not a valid operand for addx: invalid digit found in string"
+        );
+    }
+}
@@ -0,0 +1,91 @@
+//! The screen, drawn a pixel per cycle wherever the sprite is
+use crate::cpu::{Cpu, Hook};
+use anyhow::{bail, Result};
+use std::fmt;
+
+/// The sprite is 3 pixels wide, and the X register sets the horizontal position of the middle of that sprite.
+/// the CRT draws a single pixel during each cycle
+/// If the sprite is positioned such that one of its three pixels is the pixel currently being drawn,
+/// the screen produces a lit pixel (#); otherwise, the screen leaves the pixel dark (.).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Crt {
+    pub width: usize,
+    pub height: usize,
+    /// Row-major, top left first
+    pub lit: Vec<bool>,
+}
+
+impl Crt {
+    pub fn new(width: usize, height: usize) -> Self {
+        Crt {
+            width,
+            height,
+            lit: vec![false; width * height],
+        }
+    }
+
+    /// A picture drawn the way [`Crt`] displays one: `#` lit, `.` dark, a line per row
+    pub fn parse(text: &str) -> Result<Self> {
+        let rows: Vec<&str> = text
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect();
+        let width = rows.first().map_or(0, |r| r.len());
+        let mut crt = Crt::new(width, rows.len());
+        for (r, row) in rows.iter().enumerate() {
+            if row.len() != width {
+                bail!(
+                    "Row {} is {} pixels wide but row 1 is {width}",
+                    r + 1,
+                    row.len()
+                );
+            }
+            for (c, ch) in row.chars().enumerate() {
+                crt.lit[r * width + c] = match ch {
+                    '#' => true,
+                    '.' => false,
+                    _ => bail!("Row {} column {}: {ch:?} isn't a pixel", r + 1, c + 1),
+                };
+            }
+        }
+        Ok(crt)
+    }
+
+    /// Would the sprite at `x` light up the pixel drawn during `cycle`?
+    ///
+    /// Never before the first cycle, or on a screen with no width.
+    pub fn covers(&self, cycle: usize, x: isize) -> bool {
+        let Some(col) = cycle.checked_sub(1).and_then(|i| i.checked_rem(self.width)) else {
+            return false;
+        };
+        let col = col as isize;
+        x - 1 <= col && x + 1 >= col
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[bool]> {
+        self.lit.chunks(self.width.max(1))
+    }
+}
+
+impl Hook for Crt {
+    /// Anything after the bottom right pixel is off the screen
+    fn cycle(&mut self, cpu: &Cpu) {
+        let i = cpu.cycle() - 1;
+        if i < self.lit.len() {
+            self.lit[i] = self.covers(cpu.cycle(), cpu.regs.x);
+        }
+    }
+}
+
+impl fmt::Display for Crt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.rows() {
+            for p in row {
+                write!(f, "{}", if *p { '#' } else { '.' })?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,328 @@
+//! Stepping through a program by hand, watching the screen fill in
+use crate::cpu::{Cpu, Hook, Instruction};
+use crate::crt::Crt;
+use anyhow::{bail, Context, Result};
+use std::fmt;
+use std::io::{BufRead, Write};
+
+/// Where to stop, checked against the values *during* each cycle
+///
+/// An X breakpoint stops when X changes to the value, not on every cycle it stays there.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+    Cycle(usize),
+    X(isize),
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Cycle(c) => write!(f, "cycle {c}"),
+            Breakpoint::X(x) => write!(f, "X = {x}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Step(usize),
+    Continue,
+    Break(Breakpoint),
+    /// Numbered from 1, as listed
+    Delete(usize),
+    List,
+    Registers,
+    Screen,
+    Help,
+    Quit,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let number = |w: &str| -> Result<isize> {
+            w.parse().with_context(|| format!("{w:?} is not a number"))
+        };
+        let count = |w: &str| -> Result<usize> {
+            match number(w)? {
+                n if n < 0 => bail!("{n} can't be negative"),
+                n => Ok(n as usize),
+            }
+        };
+        Ok(match words.as_slice() {
+            ["s" | "step"] => Command::Step(1),
+            ["s" | "step", n] => Command::Step(count(n)?),
+            ["c" | "continue"] => Command::Continue,
+            ["b" | "break", "cycle", n] => Command::Break(Breakpoint::Cycle(count(n)?)),
+            ["b" | "break", "x", v] => Command::Break(Breakpoint::X(number(v)?)),
+            ["d" | "delete", n] => Command::Delete(count(n)?),
+            ["l" | "list"] => Command::List,
+            ["r" | "regs"] => Command::Registers,
+            ["crt" | "screen"] => Command::Screen,
+            ["h" | "help"] => Command::Help,
+            ["q" | "quit"] => Command::Quit,
+            _ => bail!("Unknown command {line:?}; try help"),
+        })
+    }
+}
+
+const HELP: &str = "\
+s, step [n]          run n cycles (default 1)
+c, continue          run until a breakpoint or the end
+b, break cycle <n>   stop during cycle n
+b, break x <v>       stop during the cycle where X becomes v
+d, delete <n>        forget breakpoint n
+l, list              show breakpoints
+r, regs              show the registers
+crt, screen          show the screen so far
+q, quit              stop debugging
+An empty line does the last step or continue again.";
+
+/// What the CPU looked like during the most recent cycle
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct During {
+    cycle: usize,
+    x: isize,
+    instruction: Instruction,
+    /// Which of its cycles this was, from 1
+    part: usize,
+}
+
+pub struct Debugger {
+    cpu: Cpu,
+    crt: Crt,
+    during: Option<During>,
+    breakpoints: Vec<Breakpoint>,
+}
+
+impl Debugger {
+    pub fn new(program: Vec<Instruction>, crt: Crt) -> Self {
+        Debugger {
+            cpu: Cpu::new(program),
+            crt,
+            during: None,
+            breakpoints: vec![],
+        }
+    }
+
+    /// One cycle, or None if the program's already over
+    fn step(&mut self) -> Option<During> {
+        let (crt, during) = (&mut self.crt, &mut self.during);
+        let mut watch = |cpu: &Cpu| {
+            crt.cycle(cpu);
+            *during = cpu.current().map(|instruction| During {
+                cycle: cpu.cycle(),
+                x: cpu.regs.x,
+                instruction,
+                part: cpu.elapsed() + 1,
+            });
+        };
+        if self.cpu.step(&mut watch) {
+            self.during
+        } else {
+            None
+        }
+    }
+
+    /// The first breakpoint this cycle hits, given X during the cycle before
+    fn hit(&self, d: &During, previous_x: isize) -> Option<usize> {
+        self.breakpoints.iter().position(|b| match b {
+            Breakpoint::Cycle(c) => *c == d.cycle,
+            Breakpoint::X(x) => *x == d.x && previous_x != d.x,
+        })
+    }
+
+    fn registers(&self, out: &mut impl Write) -> Result<()> {
+        if let Some(d) = self.during {
+            writeln!(
+                out,
+                "cycle {}: X = {} during, {} after; {} (cycle {} of {})",
+                d.cycle,
+                d.x,
+                self.cpu.regs.x,
+                d.instruction,
+                d.part,
+                d.instruction.cycles()
+            )?;
+        } else {
+            writeln!(out, "not started: X = {}", self.cpu.regs.x)?;
+        }
+        match self.cpu.current() {
+            Some(i) => writeln!(out, "next: {} at line {}", i, self.cpu.pc() + 1)?,
+            None => writeln!(out, "program finished")?,
+        }
+        Ok(())
+    }
+
+    /// The screen so far, with a `^` under the pixel last drawn
+    fn screen(&self, out: &mut impl Write) -> Result<()> {
+        let drawn = self.cpu.cycle().min(self.crt.lit.len());
+        for (r, row) in self.crt.rows().enumerate() {
+            let line: String = row
+                .iter()
+                .enumerate()
+                .map(|(c, p)| match r * self.crt.width + c {
+                    i if i >= drawn => ' ',
+                    _ if *p => '#',
+                    _ => '.',
+                })
+                .collect();
+            writeln!(out, "{}", line.trim_end())?;
+            if drawn > 0 && (drawn - 1) / self.crt.width.max(1) == r {
+                writeln!(out, "{:>1$}", "^", (drawn - 1) % self.crt.width + 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Carry out a command; false means stop
+    pub fn execute(&mut self, command: &Command, out: &mut impl Write) -> Result<bool> {
+        match command {
+            Command::Step(n) => {
+                for _ in 0..*n {
+                    if self.step().is_none() {
+                        break;
+                    }
+                }
+                self.registers(out)?;
+                self.screen(out)?;
+            }
+            Command::Continue => {
+                loop {
+                    // before the first cycle, X is what it starts as
+                    let previous_x = self.during.map_or(self.cpu.regs.x, |d| d.x);
+                    let Some(d) = self.step() else { break };
+                    if let Some(b) = self.hit(&d, previous_x) {
+                        writeln!(out, "hit breakpoint {}: {}", b + 1, self.breakpoints[b])?;
+                        break;
+                    }
+                }
+                self.registers(out)?;
+                self.screen(out)?;
+            }
+            Command::Break(b) => match self.breakpoints.iter().position(|x| x == b) {
+                Some(i) => writeln!(out, "already breakpoint {}: {b}", i + 1)?,
+                None => {
+                    self.breakpoints.push(*b);
+                    writeln!(out, "breakpoint {}: {b}", self.breakpoints.len())?;
+                }
+            },
+            Command::Delete(n) => {
+                if *n == 0 || *n > self.breakpoints.len() {
+                    writeln!(out, "no breakpoint {n}")?;
+                } else {
+                    let b = self.breakpoints.remove(n - 1);
+                    writeln!(out, "deleted {b}")?;
+                }
+            }
+            Command::List => {
+                if self.breakpoints.is_empty() {
+                    writeln!(out, "no breakpoints")?;
+                }
+                for (i, b) in self.breakpoints.iter().enumerate() {
+                    writeln!(out, "{}: {b}", i + 1)?;
+                }
+            }
+            Command::Registers => self.registers(out)?,
+            Command::Screen => self.screen(out)?,
+            Command::Help => writeln!(out, "{HELP}")?,
+            Command::Quit => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Read commands until told to quit or the input runs out
+    pub fn repl(&mut self, input: impl BufRead, out: &mut impl Write) -> Result<()> {
+        let mut last = None;
+        write!(out, "(cpu) ")?;
+        out.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            let command = if line.trim().is_empty() {
+                last.clone()
+            } else {
+                match Command::parse(&line) {
+                    Ok(c) => Some(c),
+                    Err(e) => {
+                        writeln!(out, "{e}")?;
+                        None
+                    }
+                }
+            };
+            if let Some(c) = command {
+                if !self.execute(&c, out)? {
+                    return Ok(());
+                }
+                // only moving on is worth repeating
+                last = matches!(c, Command::Step(_) | Command::Continue).then_some(c);
+            }
+            write!(out, "(cpu) ")?;
+            out.flush()?;
+        }
+        writeln!(out)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::parse_program;
+
+    fn session(commands: &str) -> Result<String> {
+        let program = parse_program(include_str!("../sample.txt"))?;
+        let mut debugger = Debugger::new(program, Crt::new(40, 6));
+        let mut out = vec![];
+        debugger.repl(commands.as_bytes(), &mut out)?;
+        Ok(String::from_utf8(out)?)
+    }
+
+    #[test]
+    fn breakpoints() -> Result<()> {
+        let out = session("b cycle 20\nb cycle 60\nc\nc\nd 1\nl\nq\nr\n")?;
+        assert!(out.contains("hit breakpoint 1: cycle 20\n"));
+        assert!(out.contains("cycle 20: X = 21 during, 21 after;"));
+        assert!(out.contains("cycle 60: X = 19 during,"));
+        assert!(out.contains("1: cycle 60\n(cpu) "));
+        // quit means quit
+        assert_eq!(out.matches("(cpu) ").count(), 7);
+
+        let out = session("break x 16\ncontinue\n")?;
+        assert!(out.contains("hit breakpoint 1: X = 16\ncycle 3: X = 16 during"));
+
+        // X stays 16 for cycle 4 as well, but only changing to it counts
+        let out = session("b x 16\nc\nc\n")?;
+        assert!(!out.contains("cycle 4:"));
+        assert!(out.contains("hit breakpoint 1: X = 16\ncycle 62: X = 16 during"));
+        // X = 1 from the start isn't a change
+        let out = session("b x 1\nc\n")?;
+        assert!(!out.contains("cycle 1:"));
+
+        // enter doesn't add the same breakpoint again, and a repeat isn't needed
+        let out = session("b cycle 20\n\nb cycle 20\nl\n")?;
+        assert!(out.contains("already breakpoint 1: cycle 20\n"));
+        assert!(out.ends_with("1: cycle 20\n(cpu) \n"));
+        Ok(())
+    }
+
+    #[test]
+    fn stepping() -> Result<()> {
+        // addx 15 then addx -11: X only changes once the first is done
+        let out = session("s\n\ns 2\nscreen\n")?;
+        assert!(out.contains("cycle 1: X = 1 during, 1 after; addx 15 (cycle 1 of 2)"));
+        assert!(out.contains("cycle 2: X = 1 during, 16 after; addx 15 (cycle 2 of 2)"));
+        assert!(out.contains("cycle 4: X = 16 during, 5 after; addx -11 (cycle 2 of 2)"));
+        assert!(out.ends_with("##..\n   ^\n\n\n\n\n\n(cpu) \n"));
+
+        let out = session("s 1000\nr\nfrobnicate\n")?;
+        assert!(out.contains("program finished"));
+        assert!(out.contains("#######.......#######.......#######.....\n"));
+        assert!(out.contains("Unknown command \"frobnicate\""));
+
+        let out = session("s -3\nd -1\n")?;
+        assert_eq!(out.matches("-3 can't be negative").count(), 1);
+        assert_eq!(out.matches("-1 can't be negative").count(), 1);
+        assert!(!out.contains("cycle 1:"));
+        Ok(())
+    }
+}
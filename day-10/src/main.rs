@@ -1,84 +1,127 @@
 //! AOC Day 10: basic CPU simulation
 
+mod asm;
+mod cpu;
+mod crt;
+mod debugger;
+mod ocr;
+mod synth;
+
 use std::io::Read;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+use cpu::{parse_program, Cpu, Hook};
+use crt::Crt;
+use debugger::Debugger;
 
 fn main() -> Result<()> {
-    let input = trace_x_during(
-        &{
-            let mut buf = String::new();
-            std::io::stdin().read_to_string(&mut buf)?;
-            buf
-        },
-        1,
-    );
+    // `debug <program>` steps through the program, taking commands from stdin;
+    // `asm <source>` and `disasm <program>` translate to and from assembly;
+    // `draw <picture>` and `write <TEXT>` make programs that draw on the screen
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        let [cmd, arg] = args.as_slice() else {
+            bail!("Usage: day-10 [debug|asm|disasm|draw|write <arg>]");
+        };
+        let read = || std::fs::read_to_string(arg);
+        let program = match cmd.as_str() {
+            "debug" => {
+                let mut debugger = Debugger::new(parse_program(&read()?)?, Crt::new(40, 6));
+                return debugger.repl(std::io::stdin().lock(), &mut std::io::stdout());
+            }
+            "disasm" => {
+                print!("{}", asm::disassemble(&parse_program(&read()?)?, 40));
+                return Ok(());
+            }
+            "asm" => asm::assemble(&read()?)?.program,
+            "draw" => synth::synthesise(&Crt::parse(&read()?)?)?,
+            "write" => synth::write(arg)?,
+            _ => bail!("Unknown command {cmd:?}: try debug, asm, disasm, draw or write"),
+        };
+        for i in program {
+            println!("{i}");
+        }
+        return Ok(());
+    }
 
-    println!("Part A: {}", part_a(&input));
-    println!("Part B:\n{}", part_b(&input, 40));
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+    let program = parse_program(&input)?;
 
-    Ok(())
-}
+    let mut hooks = (SignalStrength::default(), Crt::new(40, 6));
+    Cpu::new(program).run(&mut hooks);
+    let (signal, crt) = hooks;
 
-/// Trace the value of X "during" the cycle
-/// Two instructions, one register
-///     addx p
-///     noop
-/// `addx p` adds p (integer, which may be negative) to register X;
-/// it takes *two* cycles to complete: X does not change until one cycle later
-/// (this doesn't mean data race hell, it means it takes two cycles)
-/// X changes "after" the cycle rather than "during"
-/// `noop` does nothing and takes one cycle to complete
-fn trace_x_during(input: &str, startval: isize) -> Vec<isize> {
-    let mut x = startval;
-    let mut out = Vec::new();
-    for line in input.lines() {
-        // X traced "during" while addition happens "after"
-        out.push(x);
-        if line.starts_with("addx") {
-            // simulate two cycles with extra push for first one before X changed
-            out.push(x);
-            x += line.split_once(' ').unwrap().1.parse::<isize>().unwrap();
-        } // else noop
-    }
-    out
+    println!("Part A: {}", signal.total);
+    eprint!("{crt}");
+    println!("Part B: {}", ocr::read(&crt)?.trim_end());
+
+    Ok(())
 }
 
 /// For part A we're told that X starts with the value 1
 /// and asked to get the value of it at cycles 20, 60, 100, 140, 180 and 220,
 /// multiplied by those values and summed
-fn part_a(input: &[isize]) -> isize {
-    input
-        .iter()
-        .enumerate()
-        .map(|(i, x)| (i + 1, x))
-        .take(220)
-        .skip(19)
-        .step_by(40)
-        // .inspect(|t| eprintln!("{t:?}"))
-        .map(|(i, x)| *x * (i as isize))
-        // .inspect(|t| eprintln!("{t:?}"))
-        .sum()
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SignalStrength {
+    first: usize,
+    every: usize,
+    last: usize,
+    total: isize,
 }
 
-/// The sprite is 3 pixels wide, and the X register sets the horizontal position of the middle of that sprite.
-/// the CRT draws a single pixel during each cycle
-/// If the sprite is positioned such that one of its three pixels is the pixel currently being drawn,
-/// the screen produces a lit pixel (#); otherwise, the screen leaves the pixel dark (.).
-fn part_b(input: &[isize], width: usize) -> String {
-    let mut out = String::new();
-    for (i, x) in input.iter().enumerate() {
-        let col = (i % width) as isize;
-        // check that the current pixel is in the sprite
-        if x - 1 <= col && x + 1 >= col {
-            out.push('#')
-        } else {
-            out.push('.')
+impl Default for SignalStrength {
+    fn default() -> Self {
+        SignalStrength {
+            first: 20,
+            every: 40,
+            last: 220,
+            total: 0,
         }
-        // new line where needed
-        if col == width as isize - 1 {
-            out.push('\n');
+    }
+}
+
+impl Hook for SignalStrength {
+    fn cycle(&mut self, cpu: &Cpu) {
+        let c = cpu.cycle();
+        if c >= self.first && c <= self.last && (c - self.first).is_multiple_of(self.every) {
+            self.total += c as isize * cpu.regs.x;
         }
     }
-    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample() -> Result<()> {
+        let program = parse_program(include_str!("../sample.txt"))?;
+        let mut hooks = (SignalStrength::default(), Crt::new(40, 6));
+        Cpu::new(program).run(&mut hooks);
+        let (signal, crt) = hooks;
+        assert_eq!(signal.total, 13140);
+        assert_eq!(
+            crt.to_string(),
+            "##..##..##..##..##..##..##..##..##..##..
+###...###...###...###...###...###...###.
+####....####....####....####....####....
+#####.....#####.....#####.....#####.....
+######......######......######......####
+#######.......#######.......#######.....
+"
+        );
+        Ok(())
+    }
+    #[test]
+    fn sprite_coverage() {
+        let crt = Crt::new(40, 6);
+        // cycle 1 draws column 0, and cycle 41 starts the next row
+        assert!(crt.covers(1, 1) && crt.covers(1, 0) && crt.covers(1, -1));
+        assert!(!crt.covers(1, 2));
+        assert!(crt.covers(41, 0) && !crt.covers(40, 0));
+        // nothing's drawn before the first cycle, or on a screen with no width
+        assert!(!crt.covers(0, 0));
+        assert!(!Crt::new(0, 6).covers(1, 0));
+    }
 }
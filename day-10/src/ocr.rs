@@ -0,0 +1,173 @@
+//! Reading the letters off the screen
+use crate::crt::Crt;
+use anyhow::{bail, ensure, Result};
+
+pub const GLYPH_WIDTH: usize = 4;
+pub const GLYPH_HEIGHT: usize = 6;
+/// Each letter plus a dark column to separate it from the next
+pub const CELL_WIDTH: usize = GLYPH_WIDTH + 1;
+
+/// The capital letters that turn up in puzzle answers, row by row
+const FONT: [(char, &str); 17] = [
+    ('A', ".##.#..##..######..##..#"),
+    ('B', "###.#..####.#..##..####."),
+    ('C', ".##.#..##...#...#..#.##."),
+    ('E', "#####...###.#...#...####"),
+    ('F', "#####...###.#...#...#..."),
+    ('G', ".##.#..##...#.###..#.###"),
+    ('H', "#..##..######..##..##..#"),
+    ('I', ".###..#...#...#...#..###"),
+    ('J', "..##...#...#...##..#.##."),
+    ('K', "#..##.#.##..#.#.#.#.#..#"),
+    ('L', "#...#...#...#...#...####"),
+    ('O', ".##.#..##..##..##..#.##."),
+    ('P', "###.#..##..####.#...#..."),
+    ('R', "###.#..##..####.#.#.#..#"),
+    ('S', ".####...#....##....####."),
+    ('U', "#..##..##..##..##..#.##."),
+    ('Z', "####...#..#..#..#...####"),
+];
+
+/// The lit pixels of a letter, row-major
+pub fn glyph(letter: char) -> Option<[bool; GLYPH_WIDTH * GLYPH_HEIGHT]> {
+    let (_, pattern) = FONT.iter().find(|(c, _)| *c == letter)?;
+    let mut out = [false; GLYPH_WIDTH * GLYPH_HEIGHT];
+    for (o, p) in out.iter_mut().zip(pattern.bytes()) {
+        *o = p == b'#';
+    }
+    Some(out)
+}
+
+/// Which letter these pixels spell, if any
+fn recognise(pixels: &[bool]) -> Option<char> {
+    FONT.iter()
+        .find(|(_, pattern)| pattern.bytes().zip(pixels).all(|(p, x)| (p == b'#') == *x))
+        .map(|(c, _)| *c)
+}
+
+/// The letters across the screen, a cell of five columns each
+///
+/// The last cell may leave off its separating column. An empty cell is a space,
+/// so that text not filling the screen, like [`crate::synth::write`] draws,
+/// can be read back.
+pub fn read(crt: &Crt) -> Result<String> {
+    ensure!(
+        crt.height == GLYPH_HEIGHT,
+        "Letters are {GLYPH_HEIGHT} pixels tall but the screen has {} rows",
+        crt.height
+    );
+    let cells = crt.width.div_ceil(CELL_WIDTH);
+    ensure!(
+        cells * CELL_WIDTH - crt.width <= 1,
+        "A screen {} pixels wide doesn't divide into letters",
+        crt.width
+    );
+
+    let mut out = String::new();
+    let mut unknown = vec![];
+    for cell in 0..cells {
+        let left = cell * CELL_WIDTH;
+        let mut pixels = Vec::with_capacity(GLYPH_WIDTH * GLYPH_HEIGHT);
+        let mut spill = false;
+        for row in crt.rows() {
+            pixels.extend_from_slice(&row[left..left + GLYPH_WIDTH]);
+            spill |= row.get(left + GLYPH_WIDTH) == Some(&true);
+        }
+        match recognise(&pixels) {
+            _ if !spill && pixels.iter().all(|p| !p) => out.push(' '),
+            Some(c) if !spill => out.push(c),
+            _ => {
+                let picture: Vec<String> = crt
+                    .rows()
+                    .map(|row| {
+                        let right = (left + CELL_WIDTH).min(crt.width);
+                        row[left..right]
+                            .iter()
+                            .map(|p| if *p { '#' } else { '.' })
+                            .collect()
+                    })
+                    .collect();
+                unknown.push(format!(
+                    "cell {} (columns {}-{}):\n{}",
+                    cell + 1,
+                    left + 1,
+                    left + GLYPH_WIDTH,
+                    picture.join("\n")
+                ));
+            }
+        }
+    }
+    if !unknown.is_empty() {
+        bail!("Unrecognised letters in {}", unknown.join("\nand "));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn letters() -> Result<()> {
+        let crt = Crt::parse(
+            "####.####.###..###..###..####.####.####.
+#.......#.#..#.#..#.#..#.#.......#.#....
+###....#..###..#..#.###..###....#..###..
+#.....#...#..#.###..#..#.#.....#...#....
+#....#....#..#.#....#..#.#....#....#....
+#....####.###..#....###..#....####.#....",
+        )?;
+        assert_eq!(read(&crt)?, "FZBPBFZF");
+
+        // every letter in the font reads back as itself
+        let mut crt = Crt::new(CELL_WIDTH * FONT.len() - 1, GLYPH_HEIGHT);
+        for (i, (_, pattern)) in FONT.iter().enumerate() {
+            for (j, p) in pattern.bytes().enumerate() {
+                let (row, col) = (j / GLYPH_WIDTH, j % GLYPH_WIDTH);
+                crt.lit[row * crt.width + i * CELL_WIDTH + col] = p == b'#';
+            }
+        }
+        let all: String = FONT.iter().map(|(c, _)| c).collect();
+        assert_eq!(read(&crt)?, all);
+        Ok(())
+    }
+
+    #[test]
+    fn blank_cells() -> Result<()> {
+        // an empty cell between letters and at the end, on a 15 pixel screen
+        let crt = Crt::parse(
+            "####......####.
+#.........#....
+###.......###..
+#.........#....
+#.........#....
+#.........####.",
+        )?;
+        assert_eq!(read(&crt)?, "F E");
+        assert_eq!(read(&Crt::new(40, 6))?, " ".repeat(8));
+        Ok(())
+    }
+
+    #[test]
+    fn unrecognised() -> Result<()> {
+        let crt = Crt::parse(
+            "#..#.#..#.
+#..#..##..
+####..#...
+#..#..#...
+#..#..#...
+#..#..#...",
+        )?;
+        let e = read(&crt).unwrap_err().to_string();
+        assert!(e.starts_with("Unrecognised letters in cell 2 (columns 6-9):\n#..#.\n"));
+        assert!(!e.contains("cell 1"));
+
+        // a letter that runs into the gap isn't one
+        let crt = Crt::parse("#####\n#....\n###..\n#....\n#....\n#....")?;
+        assert!(read(&crt).unwrap_err().to_string().contains("cell 1"));
+
+        assert!(read(&Crt::new(40, 5)).is_err());
+        assert!(read(&Crt::new(38, 6)).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+//! Working backwards from a picture to a program that draws it
+use crate::asm::draw;
+use crate::cpu::{Cpu, Instruction, Registers};
+use crate::crt::Crt;
+use crate::ocr::{glyph, CELL_WIDTH, GLYPH_HEIGHT, GLYPH_WIDTH};
+use anyhow::{bail, ensure, Context, Result};
+
+/// A program that draws exactly `target`, checked by running it
+///
+/// Not every picture can be drawn: X starts at 1 and can't change before
+/// the third cycle, so the first two pixels are always lit, and since it
+/// takes two cycles to move, some runs of pixels can't follow one another.
+pub fn synthesise(target: &Crt) -> Result<Vec<Instruction>> {
+    let x = Registers::default().x;
+    let Some(program) = draw(0, x, &target.lit, target.width) else {
+        // the longest start that can be drawn ends just before the trouble
+        let (mut lo, mut hi) = (0, target.lit.len());
+        while lo + 1 < hi {
+            let mid = (lo + hi) / 2;
+            if draw(0, x, &target.lit[..mid], target.width).is_some() {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        bail!(
+            "This CPU can't draw that picture: row {} column {} can't follow the pixels before it",
+            lo / target.width.max(1) + 1,
+            lo % target.width.max(1) + 1
+        );
+    };
+
+    let mut crt = Crt::new(target.width, target.height);
+    Cpu::new(program.clone()).run(&mut crt);
+    if crt != *target {
+        bail!("The program draws\n{crt}instead of\n{target}");
+    }
+    Ok(program)
+}
+
+/// `text` in the puzzle's font, one letter per five columns from the left
+pub fn render(text: &str, width: usize) -> Result<Crt> {
+    let mut crt = Crt::new(width, GLYPH_HEIGHT);
+    ensure!(
+        text.chars().count() * CELL_WIDTH <= width + 1,
+        "{text:?} doesn't fit on a screen {width} pixels wide"
+    );
+    for (i, c) in text.chars().enumerate() {
+        let g = glyph(c).with_context(|| format!("There's no {c:?} in the font"))?;
+        for (j, p) in g.into_iter().enumerate() {
+            let (row, col) = (j / GLYPH_WIDTH, j % GLYPH_WIDTH);
+            crt.lit[row * width + i * CELL_WIDTH + col] = p;
+        }
+    }
+    Ok(crt)
+}
+
+/// A program that writes `text` on the usual 40 by 6 screen
+pub fn write(text: &str) -> Result<Vec<Instruction>> {
+    synthesise(&render(text, 40)?).with_context(|| format!("Can't write {text:?}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::parse_program;
+    use crate::ocr;
+
+    #[test]
+    fn round_trips() -> Result<()> {
+        // the puzzle's own picture
+        let mut crt = Crt::new(40, 6);
+        Cpu::new(parse_program(include_str!("../input.txt"))?).run(&mut crt);
+        let program = synthesise(&crt)?;
+        let mut again = Crt::new(40, 6);
+        Cpu::new(program).run(&mut again);
+        assert_eq!(again, crt);
+
+        // and some words, read back by the OCR
+        for text in ["FZBPBFZF", "ZEBRA", "PLUCK", "EGGS", "FORKS", "RUSH", "B"] {
+            let mut crt = Crt::new(40, 6);
+            Cpu::new(write(text)?).run(&mut crt);
+            assert_eq!(ocr::read(&crt)?.trim_end_matches(' '), text);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn impossible() {
+        // the first two pixels are always lit
+        let e = write("HELLO").unwrap_err();
+        assert_eq!(
+            format!("{e:#}"),
+            "Can't write \"HELLO\": This CPU can't draw that picture: \
+             row 1 column 2 can't follow the pixels before it"
+        );
+        assert!(write("FOOLSTOOLONG").is_err());
+        assert!(write("F?").is_err());
+    }
+}
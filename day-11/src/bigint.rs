@@ -0,0 +1,332 @@
+//! Just enough of arbitrarily large integers to follow worry levels all the way up
+use crate::expr::{Op, Value};
+use anyhow::{Context, Result};
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Sign and magnitude, the magnitude in base 2^32 with the least significant
+/// limb first and no zero limbs on the end, so zero has no limbs and is never
+/// negative
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Big {
+    negative: bool,
+    limbs: Vec<u32>,
+}
+
+impl Big {
+    fn new(negative: bool, mut limbs: Vec<u32>) -> Self {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        Big {
+            negative: negative && !limbs.is_empty(),
+            limbs,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    /// Rounding towards zero, with the remainder taking the sign of `self`,
+    /// just as `isize` does; None when dividing by zero
+    pub fn div_rem(&self, d: &Big) -> Option<(Big, Big)> {
+        if d.is_zero() {
+            return None;
+        }
+        let (q, r) = div_rem(&self.limbs, &d.limbs);
+        Some((
+            Big::new(self.negative != d.negative, q),
+            Big::new(self.negative, r),
+        ))
+    }
+
+    /// The remainder on dividing by `d`, from 0 up to but not including `d`,
+    /// which monkeys check is positive when they're parsed
+    pub fn rem_euclid(&self, d: isize) -> isize {
+        debug_assert!(d > 0, "rem_euclid by {d}");
+        let d = d as u128;
+        let r = self
+            .limbs
+            .iter()
+            .rev()
+            .fold(0, |r, &l| ((r << 32) | l as u128) % d) as isize;
+        if self.negative && r != 0 {
+            d as isize - r
+        } else {
+            r
+        }
+    }
+
+    /// How many decimal digits, not counting any sign
+    pub fn digits(&self) -> usize {
+        self.to_string().trim_start_matches('-').len()
+    }
+}
+
+impl From<isize> for Big {
+    fn from(n: isize) -> Self {
+        let m = n.unsigned_abs() as u64;
+        Big::new(n < 0, vec![m as u32, (m >> 32) as u32])
+    }
+}
+
+impl TryFrom<&Big> for isize {
+    type Error = anyhow::Error;
+
+    fn try_from(b: &Big) -> Result<isize> {
+        let too_big = || format!("{b} doesn't fit in an isize");
+        anyhow::ensure!(b.limbs.len() <= 2, too_big());
+        let m = b
+            .limbs
+            .iter()
+            .rev()
+            .fold(0u64, |m, &l| (m << 32) | l as u64);
+        let n = if b.negative {
+            0i64.checked_sub_unsigned(m)
+        } else {
+            i64::try_from(m).ok()
+        };
+        n.and_then(|n| isize::try_from(n).ok())
+            .with_context(too_big)
+    }
+}
+
+impl Ord for Big {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp(&self.limbs, &other.limbs),
+            (true, true) => cmp(&other.limbs, &self.limbs),
+        }
+    }
+}
+
+impl PartialOrd for Big {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Big {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        // nine digits at a time, least significant first
+        const CHUNK: u32 = 1_000_000_000;
+        let mut chunks = vec![];
+        let mut rest = self.limbs.clone();
+        while !rest.is_empty() {
+            let (q, r) = div_rem(&rest, &[CHUNK]);
+            chunks.push(r.first().copied().unwrap_or(0));
+            rest = Big::new(false, q).limbs;
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        let mut chunks = chunks.into_iter().rev();
+        write!(f, "{}", chunks.next().unwrap_or(0))?;
+        for c in chunks {
+            write!(f, "{c:09}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Value for Big {
+    fn constant(c: isize) -> Self {
+        Big::from(c)
+    }
+
+    fn apply(op: Op, a: &Self, b: &Self) -> Result<Self> {
+        Ok(match op {
+            Op::Add => add(a, b),
+            Op::Sub => add(a, &Big::new(!b.negative, b.limbs.clone())),
+            Op::Mul => Big::new(a.negative != b.negative, mul(&a.limbs, &b.limbs)),
+            Op::Div => a.div_rem(b).context("Division by zero")?.0,
+            Op::Rem => a.div_rem(b).context("Division by zero")?.1,
+        })
+    }
+
+    fn rem_euclid(&self, d: isize) -> isize {
+        Big::rem_euclid(self, d)
+    }
+}
+
+fn add(a: &Big, b: &Big) -> Big {
+    if a.negative == b.negative {
+        return Big::new(a.negative, add_mag(&a.limbs, &b.limbs));
+    }
+    // opposite signs: the larger magnitude wins
+    match cmp(&a.limbs, &b.limbs) {
+        Ordering::Less => Big::new(b.negative, sub_mag(&b.limbs, &a.limbs)),
+        _ => Big::new(a.negative, sub_mag(&a.limbs, &b.limbs)),
+    }
+}
+
+fn cmp(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0u64;
+    for i in 0..a.len().max(b.len()) {
+        let s = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        out.push(s as u32);
+        carry = s >> 32;
+    }
+    out.push(carry as u32);
+    out
+}
+
+/// `a - b`, where `a` is at least `b`
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &x) in a.iter().enumerate() {
+        let mut d = x as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = (d < 0) as i64;
+        if d < 0 {
+            d += 1 << 32;
+        }
+        out.push(d as u32);
+    }
+    out
+}
+
+fn mul(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let t = x as u64 * y as u64 + out[i + j] as u64 + carry;
+            out[i + j] = t as u32;
+            carry = t >> 32;
+        }
+        out[i + b.len()] = carry as u32;
+    }
+    out
+}
+
+/// Quotient and remainder of magnitudes, `d` being non-zero
+///
+/// One limb at a time for a small divisor, otherwise one bit at a time, which
+/// is slow but only `old / old` and the like need it.
+fn div_rem(n: &[u32], d: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if let [d] = d {
+        let d = *d as u64;
+        let mut q = vec![0u32; n.len()];
+        let mut r = 0u64;
+        for i in (0..n.len()).rev() {
+            let t = (r << 32) | n[i] as u64;
+            q[i] = (t / d) as u32;
+            r = t % d;
+        }
+        return (q, vec![r as u32]);
+    }
+
+    let mut q = vec![0u32; n.len()];
+    let mut r: Vec<u32> = vec![];
+    for bit in (0..n.len() * 32).rev() {
+        // r = r * 2 + the next bit of n
+        r = add_mag(&r, &r);
+        r[0] |= (n[bit / 32] >> (bit % 32)) & 1;
+        while r.last() == Some(&0) {
+            r.pop();
+        }
+        if cmp(&r, d) != Ordering::Less {
+            r = sub_mag(&r, d);
+            while r.last() == Some(&0) {
+                r.pop();
+            }
+            q[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+    (q, r)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn big(n: isize) -> Big {
+        Big::from(n)
+    }
+
+    #[test]
+    fn agrees_with_isize() -> Result<()> {
+        let values = [
+            0,
+            1,
+            -1,
+            7,
+            -7,
+            19,
+            1 << 31,
+            -(1 << 32),
+            1 << 40,
+            123_456_789_012,
+            -98_765_432_101,
+            isize::MAX,
+            isize::MIN + 1,
+        ];
+        for a in values {
+            assert_eq!(big(a).to_string(), a.to_string());
+            assert_eq!(isize::try_from(&big(a))?, a);
+            for b in values {
+                for op in [Op::Add, Op::Sub, Op::Mul, Op::Div, Op::Rem] {
+                    let want = isize::apply(op, &a, &b);
+                    let got = Big::apply(op, &big(a), &big(b));
+                    match want {
+                        Ok(w) => assert_eq!(isize::try_from(&got?)?, w, "{a} {op:?} {b}"),
+                        // too big for isize is fine here, but not dividing by zero
+                        Err(_) if b == 0 => assert!(got.is_err()),
+                        Err(_) => assert!(isize::try_from(&got?).is_err(), "{a} {op:?} {b}"),
+                    }
+                }
+                if b > 0 {
+                    assert_eq!(big(a).rem_euclid(b), a.rem_euclid(b));
+                }
+                assert_eq!(big(a).cmp(&big(b)), a.cmp(&b));
+            }
+        }
+        assert_eq!(big(isize::MIN).to_string(), isize::MIN.to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn really_big() -> Result<()> {
+        // 2^200 and 10^40, worked out elsewhere
+        let mut two = big(1);
+        for _ in 0..200 {
+            two = Big::apply(Op::Mul, &two, &big(2))?;
+        }
+        assert_eq!(
+            two.to_string(),
+            "1606938044258990275541962092341162602522202993782792835301376"
+        );
+        let ten = (0..40).try_fold(big(1), |t, _| Big::apply(Op::Mul, &t, &big(10)))?;
+        assert_eq!(ten.digits(), 41);
+
+        // division undoes multiplication, remainder and all
+        let n = Big::apply(Op::Add, &Big::apply(Op::Mul, &two, &ten)?, &big(12345))?;
+        assert_eq!(n.div_rem(&ten), Some((two.clone(), big(12345))));
+        assert_eq!(n.div_rem(&two), Some((ten.clone(), big(12345))));
+        let neg = Big::apply(Op::Sub, &big(0), &n)?;
+        assert_eq!(
+            neg.div_rem(&two),
+            Some((Big::apply(Op::Sub, &big(0), &ten)?, big(-12345)))
+        );
+        assert_eq!(n.rem_euclid(10_000), 2345);
+        assert_eq!(neg.rem_euclid(10_000), 10_000 - 2345);
+        assert!(Big::apply(Op::Sub, &n, &n)?.is_zero());
+        assert!(isize::try_from(&two).is_err());
+        assert!(neg < big(isize::MIN) && two > ten);
+        Ok(())
+    }
+}
@@ -0,0 +1,300 @@
+//! The little arithmetic language of `Operation: new = ...`
+use anyhow::{bail, ensure, Context, Result};
+use std::fmt;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    /// Rounds towards zero
+    Div,
+    /// Takes the sign of the left side, as Rust's `%` does
+    Rem,
+}
+
+impl Op {
+    fn symbol(&self) -> char {
+        match self {
+            Op::Add => '+',
+            Op::Sub => '-',
+            Op::Mul => '*',
+            Op::Div => '/',
+            Op::Rem => '%',
+        }
+    }
+
+    /// Higher binds tighter
+    fn precedence(&self) -> u8 {
+        match self {
+            Op::Add | Op::Sub => 1,
+            Op::Mul | Op::Div | Op::Rem => 2,
+        }
+    }
+}
+
+/// What a monkey does to the worry level, `old`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Expr {
+    Old,
+    Const(isize),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn parse(input: &str) -> Result<Self> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.sum()?;
+        if let Some(t) = parser.tokens.get(parser.pos) {
+            bail!("Unexpected {t} in {input:?}");
+        }
+        Ok(expr)
+    }
+
+    pub fn eval<V: Value>(&self, old: &V) -> Result<V> {
+        Ok(match self {
+            Expr::Old => old.clone(),
+            Expr::Const(c) => V::constant(*c),
+            Expr::Binary(op, a, b) => V::apply(*op, &a.eval(old)?, &b.eval(old)?)?,
+        })
+    }
+
+    /// Built only from `+`, `-` and `*`, so working modulo anything gives
+    /// the same remainder as working it out in full
+    pub fn is_ring(&self) -> bool {
+        match self {
+            Expr::Old | Expr::Const(_) => true,
+            Expr::Binary(Op::Div | Op::Rem, _, _) => false,
+            Expr::Binary(_, a, b) => a.is_ring() && b.is_ring(),
+        }
+    }
+}
+
+/// Something worry levels can be made of
+pub trait Value: Clone + PartialEq + fmt::Display {
+    fn constant(c: isize) -> Self;
+    fn apply(op: Op, a: &Self, b: &Self) -> Result<Self>;
+    /// The remainder on dividing by `d`, between 0 and |d|
+    fn rem_euclid(&self, d: isize) -> isize;
+}
+
+/// Overflow is an error rather than wrapping round
+impl Value for isize {
+    fn constant(c: isize) -> Self {
+        c
+    }
+
+    fn apply(op: Op, a: &Self, b: &Self) -> Result<Self> {
+        let checked = match op {
+            Op::Add => a.checked_add(*b),
+            Op::Sub => a.checked_sub(*b),
+            Op::Mul => a.checked_mul(*b),
+            Op::Div | Op::Rem if *b == 0 => bail!("Division by zero"),
+            Op::Div => a.checked_div(*b),
+            Op::Rem => a.checked_rem(*b),
+        };
+        checked.with_context(|| format!("{a} {} {b} overflows an isize", op.symbol()))
+    }
+
+    fn rem_euclid(&self, d: isize) -> isize {
+        isize::rem_euclid(*self, d)
+    }
+}
+
+/// With only the brackets it needs
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Old => write!(f, "old"),
+            Expr::Const(c) => write!(f, "{c}"),
+            Expr::Binary(op, a, b) => {
+                let tighter = |e: &Expr, strictly: bool| match e {
+                    Expr::Binary(o, _, _) if strictly => o.precedence() <= op.precedence(),
+                    Expr::Binary(o, _, _) => o.precedence() < op.precedence(),
+                    _ => false,
+                };
+                // left to right, so only the right side needs brackets at the same level
+                if tighter(a, false) {
+                    write!(f, "({a})")?;
+                } else {
+                    write!(f, "{a}")?;
+                }
+                write!(f, " {} ", op.symbol())?;
+                if tighter(b, true) {
+                    write!(f, "({b})")
+                } else {
+                    write!(f, "{b}")
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Old,
+    Number(isize),
+    Op(Op),
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Old => write!(f, "'old'"),
+            Token::Number(n) => write!(f, "'{n}'"),
+            Token::Op(op) => write!(f, "'{}'", op.symbol()),
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut out = vec![];
+    let mut chars = input.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        out.push(match c {
+            c if c.is_whitespace() => continue,
+            '+' => Token::Op(Op::Add),
+            '-' => Token::Op(Op::Sub),
+            '*' => Token::Op(Op::Mul),
+            '/' => Token::Op(Op::Div),
+            '%' => Token::Op(Op::Rem),
+            '(' => Token::Open,
+            ')' => Token::Close,
+            c if c.is_ascii_alphanumeric() => {
+                let mut end = i + c.len_utf8();
+                while let Some((j, d)) = chars.next_if(|(_, d)| d.is_ascii_alphanumeric()) {
+                    end = j + d.len_utf8();
+                }
+                match &input[i..end] {
+                    "old" => Token::Old,
+                    word => Token::Number(
+                        word.parse()
+                            .with_context(|| format!("{word:?} is neither a number nor old"))?,
+                    ),
+                }
+            }
+            _ => bail!("Unexpected {c:?} in {input:?}"),
+        });
+    }
+    Ok(out)
+}
+
+/// Recursive descent, one function per level of precedence
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next_op(&mut self, level: u8) -> Option<Op> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) if op.precedence() == level => {
+                self.pos += 1;
+                Some(*op)
+            }
+            _ => None,
+        }
+    }
+
+    /// Terms added or taken away
+    fn sum(&mut self) -> Result<Expr> {
+        let mut e = self.product()?;
+        while let Some(op) = self.next_op(1) {
+            e = Expr::Binary(op, Box::new(e), Box::new(self.product()?));
+        }
+        Ok(e)
+    }
+
+    /// Factors multiplied, divided or taken the remainder of
+    fn product(&mut self) -> Result<Expr> {
+        let mut e = self.factor()?;
+        while let Some(op) = self.next_op(2) {
+            e = Expr::Binary(op, Box::new(e), Box::new(self.factor()?));
+        }
+        Ok(e)
+    }
+
+    fn factor(&mut self) -> Result<Expr> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        Ok(match token {
+            Some(Token::Old) => Expr::Old,
+            Some(Token::Number(n)) => Expr::Const(n),
+            // a minus sign in front of a number is part of it
+            Some(Token::Op(Op::Sub)) => match self.factor()? {
+                Expr::Const(n) => Expr::Const(-n),
+                e => Expr::Binary(Op::Sub, Box::new(Expr::Const(0)), Box::new(e)),
+            },
+            Some(Token::Open) => {
+                let e = self.sum()?;
+                ensure!(
+                    self.tokens.get(self.pos) == Some(&Token::Close),
+                    "Missing ')'"
+                );
+                self.pos += 1;
+                e
+            }
+            Some(t) => bail!("Expected a number, old or '(' but found {t}"),
+            None => bail!("Expression ends too soon"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn evaluation() -> Result<()> {
+        for (text, old, new) in [
+            ("old * 19", 79, 1501),
+            ("old + old", 4, 8),
+            ("old * old", 9, 81),
+            ("3 + old * 2", 5, 13),
+            ("(3 + old) * 2", 5, 16),
+            ("old - 10 - 5", 20, 5),
+            ("old / 3 % 4", 20, 2),
+            ("-old % 7", 10, -3),
+            ("(old*old)-(old - -2)", 4, 10),
+        ] {
+            assert_eq!(Expr::parse(text)?.eval(&old)?, new, "{text}");
+        }
+        assert!(Expr::parse("old / (old - old)")?.eval(&1).is_err());
+        let e = Expr::parse("old * old")?.eval(&(1isize << 32)).unwrap_err();
+        assert_eq!(e.to_string(), "4294967296 * 4294967296 overflows an isize");
+        Ok(())
+    }
+
+    #[test]
+    fn printing() -> Result<()> {
+        for (text, printed) in [
+            ("old * 19", "old * 19"),
+            ("(old + 1) * (old - 2)", "(old + 1) * (old - 2)"),
+            ("old - (old - 1)", "old - (old - 1)"),
+            ("(old - old) - 1", "old - old - 1"),
+            ("((old))*((3))", "old * 3"),
+            ("old % (2 * 3)", "old % (2 * 3)"),
+        ] {
+            let e = Expr::parse(text)?;
+            assert_eq!(e.to_string(), printed);
+            assert_eq!(Expr::parse(printed)?, e);
+        }
+        assert!(Expr::parse("old * old + 3")?.is_ring());
+        assert!(!Expr::parse("(old + 1) / 2")?.is_ring());
+        Ok(())
+    }
+
+    #[test]
+    fn errors() {
+        for bad in [
+            "", "old +", "old ^ 2", "(old + 1", "old old", "new * 2", "old + )",
+        ] {
+            assert!(Expr::parse(bad).is_err(), "{bad:?}");
+        }
+    }
+}
@@ -1,7 +1,13 @@
 /// Monkey in the Middle
-use std::{collections::VecDeque, io::Read};
+use std::{collections::VecDeque, fmt, io::Read};
 
-use anyhow::Result;
+use anyhow::{bail, ensure, Context, Result};
+
+mod bigint;
+mod expr;
+
+use bigint::Big;
+use expr::{Expr, Value};
 
 fn main() -> Result<()> {
     let instr = {
@@ -10,160 +16,347 @@ fn main() -> Result<()> {
         buf
     };
 
-    println!("Part A: {}", part_a(&instr, 20));
+    // `exact <rounds>` checks part B against worry levels that are never cut down
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        let [cmd, rounds] = args.as_slice() else {
+            bail!("Usage: day-11 [exact <rounds>]");
+        };
+        ensure!(
+            cmd == "exact",
+            "Unknown command {cmd:?}: try exact <rounds>"
+        );
+        let rounds = rounds
+            .parse()
+            .with_context(|| format!("{rounds:?} rounds"))?;
+        let exact = part_b_exact(&instr, rounds)?;
+        println!(
+            "{rounds} rounds agree: monkey business {}, worry up to {} digits",
+            exact.business, exact.digits
+        );
+        return Ok(());
+    }
+
+    println!("Part A: {}", part_a(&instr, 20)?);
 
-    println!("Part B: {}", part_b(&instr, 10_000));
+    println!("Part B: {}", part_b(&instr, 10_000)?);
 
     Ok(())
 }
 
-fn parse_input(input: &str) -> (Vec<Monkey>, isize) {
+/// The monkeys, and the product of everything they test divisibility by
+fn parse_input(input: &str) -> Result<(Vec<Monkey>, isize)> {
     let mut out = Vec::new();
-    let mut modulo = 1;
-    for (k, l) in input.split("\n\n").enumerate() {
+    let mut modulo: isize = 1;
+    for (k, l) in input.trim().split("\n\n").enumerate() {
+        let monkey = Monkey::parse(l).with_context(|| format!("Monkey {k}"))?;
+        modulo = modulo
+            .checked_mul(monkey.divisor)
+            .context("The divisors multiply to more than fits in an isize")?;
+        out.push(monkey);
+    }
+    for (k, m) in out.iter().enumerate() {
+        for target in [m.if_true, m.if_false] {
+            ensure!(
+                target < out.len() && target != k,
+                "Monkey {k} throws to monkey {target}"
+            );
+        }
+    }
+
+    Ok((out, modulo))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Monkey {
+    items: VecDeque<isize>,
+    operation: Expr,
+    divisor: isize,
+    if_true: usize,
+    if_false: usize,
+}
+
+impl Monkey {
+    fn parse(block: &str) -> Result<Self> {
         let mut items = VecDeque::new();
-        let mut operation: Box<dyn Fn(isize) -> isize> = Box::new(|x| x);
-        let mut testmod: isize = 1;
-        let mut iftrue: usize = 0;
-        let mut iffalse: usize = 0;
-        for line in l.lines().skip(1).map(|s| s.trim()) {
-            if let Some(starting_items) = line.strip_prefix("Starting items: ") {
-                items.extend(
-                    starting_items
-                        .split(",")
-                        .map(|s| s.trim())
-                        .filter_map(|s| s.parse::<isize>().ok()),
-                );
-            } else if let Some(oppy) = line.strip_prefix("Operation: new = old ") {
-                let (op, val_s) = oppy.split_once(' ').unwrap();
-                // eprintln!("op: {op}\t val_s: {val_s}");
-                if let Ok(val) = val_s.parse::<isize>() {
-                    operation = match op {
-                        "+" => Box::from(move |x| x + val),
-                        "*" => Box::from(move |x| x * val),
-                        _ => todo!(),
-                    }
-                } else {
-                    operation = match op {
-                        "+" => Box::from(|x| x + x),
-                        "*" => Box::from(|x| x * x),
-                        _ => todo!(),
-                    }
+        let mut operation = None;
+        let mut divisor = None;
+        let mut if_true = None;
+        let mut if_false = None;
+        for line in block.lines().skip(1).map(|s| s.trim()) {
+            if let Some(starting_items) = line.strip_prefix("Starting items:") {
+                for s in starting_items
+                    .split(',')
+                    .map(|s| s.trim())
+                    .filter(|s| !s.is_empty())
+                {
+                    items.push_back(s.parse().with_context(|| format!("Item {s:?}"))?);
                 }
+            } else if let Some(oppy) = line.strip_prefix("Operation: new = ") {
+                operation = Some(Expr::parse(oppy)?);
             } else if let Some(testy) = line.strip_prefix("Test: divisible by ") {
-                testmod = testy.parse().unwrap();
-                modulo *= testmod;
+                let d: isize = testy
+                    .parse()
+                    .with_context(|| format!("Divisor {testy:?}"))?;
+                ensure!(d > 0, "Can't test divisibility by {d}");
+                divisor = Some(d);
             } else if let Some(iffy) = line.strip_prefix("If true: throw to monkey ") {
-                iftrue = iffy.parse().unwrap();
+                if_true = Some(iffy.parse().with_context(|| format!("Monkey {iffy:?}"))?);
             } else if let Some(iffy) = line.strip_prefix("If false: throw to monkey ") {
-                iffalse = iffy.parse().unwrap();
+                if_false = Some(iffy.parse().with_context(|| format!("Monkey {iffy:?}"))?);
+            } else if !line.is_empty() {
+                bail!("Unexpected line {line:?}");
             }
         }
 
-        let test = Box::from(move |x| if x % testmod == 0 { iftrue } else { iffalse });
+        Ok(Monkey {
+            items,
+            operation: operation.context("No operation")?,
+            divisor: divisor.context("No test")?,
+            if_true: if_true.context("Nowhere to throw when true")?,
+            if_false: if_false.context("Nowhere to throw when false")?,
+        })
+    }
 
-        out.insert(
-            k,
-            Monkey {
-                items,
-                operation,
-                test,
-            },
-        )
+    fn target(&self, worry: &impl Value) -> usize {
+        if worry.rem_euclid(self.divisor) == 0 {
+            self.if_true
+        } else {
+            self.if_false
+        }
     }
+}
 
-    (out, modulo)
+/// As in the puzzle, less the header
+impl fmt::Display for Monkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let items: Vec<String> = self.items.iter().map(|i| i.to_string()).collect();
+        writeln!(f, "  Starting items: {}", items.join(", "))?;
+        writeln!(f, "  Operation: new = {}", self.operation)?;
+        writeln!(f, "  Test: divisible by {}", self.divisor)?;
+        writeln!(f, "    If true: throw to monkey {}", self.if_true)?;
+        writeln!(f, "    If false: throw to monkey {}", self.if_false)
+    }
 }
 
-struct Monkey<'a> {
-    items: VecDeque<isize>,
-    operation: Box<dyn Fn(isize) -> isize + 'a>,
-    test: Box<dyn Fn(isize) -> usize + 'a>,
+/// Who's holding what, and how many items each monkey has inspected
+struct Game<'a, V> {
+    monkeys: &'a [Monkey],
+    items: Vec<VecDeque<V>>,
+    counts: Vec<usize>,
+    round: usize,
 }
 
-fn part_a(input: &str, rounds: usize) -> usize {
-    let mut monkeys = parse_input(input).0;
-    let mc = monkeys.len();
-
-    let mut counts = vec![0_usize; mc];
-
-    for _roundn in 1..=rounds {
-        // eprintln!("Round {_roundn}");
-        for k in 0..mc {
-            let mut throws: Vec<(usize, isize)> = Vec::new();
-            {
-                let m = &mut monkeys[k];
-                // eprintln!("Monkey {k}");
-                while let Some(item) = m.items.pop_front() {
-                    counts[k] += 1;
-                    // eprintln!("\tMonkey inspects an item with a worry level of {item}");
-                    let worry = (m.operation)(item);
-                    // eprintln!("\tWorry level is now {worry}");
-                    let worry = worry / 3;
-                    // eprintln!("\tMonkey gets bored with item. Worry level is divided by 3 to {worry}");
-                    let nextmonkey = (m.test)(worry);
-                    // eprintln!("\tItem is thrown to monkey {nextmonkey}");
-                    throws.push((nextmonkey, worry));
-                }
-                for (n, w) in throws {
-                    monkeys[n].items.push_back(w);
-                }
+impl<'a, V: Value> Game<'a, V> {
+    fn new(monkeys: &'a [Monkey]) -> Result<Self> {
+        ensure!(monkeys.len() >= 2, "Monkey business needs two monkeys");
+        Ok(Game {
+            monkeys,
+            items: monkeys
+                .iter()
+                .map(|m| m.items.iter().map(|&i| V::constant(i)).collect())
+                .collect(),
+            counts: vec![0; monkeys.len()],
+            round: 0,
+        })
+    }
+
+    /// Every monkey takes a turn, with `relief` applied to each worry level
+    /// after the monkey's operation
+    fn round(&mut self, relief: &impl Fn(V) -> V) -> Result<()> {
+        self.round += 1;
+        for (k, m) in self.monkeys.iter().enumerate() {
+            while let Some(item) = self.items[k].pop_front() {
+                self.counts[k] += 1;
+                let worry = m.operation.eval(&item).with_context(|| {
+                    format!(
+                        "Round {}: monkey {k} doing new = {} to {item}",
+                        self.round, m.operation
+                    )
+                })?;
+                let worry = relief(worry);
+                self.items[m.target(&worry)].push_back(worry);
             }
         }
-        // for (k, m) in monkeys.iter().enumerate() {
-        //     eprintln!("Monkey {k}: {:?}", m.items);
-        // }
+        Ok(())
     }
 
-    // for (i, c) in counts.iter().enumerate() {
-    //     eprintln!("Monkey {i} inspected items {c} times.");
-    // }
+    /// The two busiest monkeys' inspection counts multiplied
+    fn business(&self) -> usize {
+        let mut counts = self.counts.clone();
+        counts.sort_by(|a, b| b.cmp(a));
+        counts[0] * counts[1]
+    }
+}
 
-    counts.sort_by(|a, b| b.cmp(a));
+fn play(monkeys: &[Monkey], rounds: usize, relief: impl Fn(isize) -> isize) -> Result<usize> {
+    let mut game = Game::new(monkeys)?;
+    for _ in 0..rounds {
+        game.round(&relief)?;
+    }
+    Ok(game.business())
+}
 
-    counts[0] * counts[1]
+fn part_a(input: &str, rounds: usize) -> Result<usize> {
+    play(&parse_input(input)?.0, rounds, |worry| worry / 3)
 }
 
 /// Like part A but without the div-3
-fn part_b(input: &str, rounds: usize) -> usize {
-    let (mut monkeys, modulo) = parse_input(input);
-    let mc = monkeys.len();
-
-    let mut counts = vec![0_usize; mc];
-
-    for _roundn in 1..=rounds {
-        // eprintln!("Round {_roundn}");
-        for k in 0..mc {
-            let mut throws: Vec<(usize, isize)> = Vec::new();
-            {
-                let m = &mut monkeys[k];
-                // eprintln!("Monkey {k}");
-                while let Some(item) = m.items.pop_front() {
-                    counts[k] += 1;
-                    // eprintln!("\tMonkey inspects an item with a worry level of {item}");
-                    let worry = (m.operation)(item);
-                    // eprintln!("\tWorry level is now {worry}");
-                    // For part B we now take a modulo
-                    let worry = worry % modulo;
-                    // eprintln!("\tMonkey gets bored with item. Worry level is divided by 3 to {worry}");
-                    let nextmonkey = (m.test)(worry);
-                    // eprintln!("\tItem is thrown to monkey {nextmonkey}");
-                    throws.push((nextmonkey, worry));
-                }
-                for (n, w) in throws {
-                    monkeys[n].items.push_back(w);
-                }
-            }
+fn part_b(input: &str, rounds: usize) -> Result<usize> {
+    let (monkeys, modulo) = parse_input(input)?;
+    // Only + - * commute with taking a modulo, so the tests still see the
+    // same remainders; / and % would see something else
+    for (k, m) in monkeys.iter().enumerate() {
+        ensure!(
+            m.operation.is_ring(),
+            "Monkey {k}'s worry levels can't be kept small: new = {}",
+            m.operation
+        );
+    }
+    play(&monkeys, rounds, |worry| worry.rem_euclid(modulo))
+}
+
+/// Part B in full, with nothing taken off the worry levels
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Exact {
+    business: usize,
+    /// Decimal digits in the largest worry level at the end
+    digits: usize,
+}
+
+/// Plays part B with worry levels of any size alongside the usual way,
+/// checking after every round that each monkey has inspected as many items
+/// and is holding the same ones, give or take the modulo
+///
+/// The numbers get huge quickly, so this is only for a few rounds.
+fn part_b_exact(input: &str, rounds: usize) -> Result<Exact> {
+    let (monkeys, modulo) = parse_input(input)?;
+    let mut exact = Game::<Big>::new(&monkeys)?;
+    let mut reduced = Game::<isize>::new(&monkeys)?;
+    for r in 1..=rounds {
+        exact.round(&|worry| worry)?;
+        reduced.round(&|worry| worry.rem_euclid(modulo))?;
+        ensure!(
+            exact.counts == reduced.counts,
+            "Round {r}: inspections are {:?} in full but {:?} modulo {modulo}",
+            exact.counts,
+            reduced.counts
+        );
+        for (k, (full, small)) in exact.items.iter().zip(&reduced.items).enumerate() {
+            let agree = full.len() == small.len()
+                && full
+                    .iter()
+                    .zip(small)
+                    .all(|(f, s)| f.rem_euclid(modulo) == *s);
+            ensure!(
+                agree,
+                "Round {r}: monkey {k} holds {} in full but {small:?} modulo {modulo}",
+                full.iter()
+                    .map(|f| f.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
         }
-        // if _roundn == 20 || _roundn % 1000 == 0 {
-        //     eprintln!("Round {_roundn}");
-        //     for (i, c) in counts.iter().enumerate() {
-        //         eprintln!("\tMonkey {i} inspected items {c} times.");
-        //     }
-        // }
     }
+    let digits = exact.items.iter().flatten().map(|w| w.digits()).max();
+    Ok(Exact {
+        business: exact.business(),
+        digits: digits.unwrap_or(0),
+    })
+}
 
-    counts.sort_by(|a, b| b.cmp(a));
+#[cfg(test)]
+mod test {
+    use super::*;
 
-    counts[0] * counts[1]
+    #[test]
+    fn sample() -> Result<()> {
+        let input = include_str!("../sample.txt");
+        assert_eq!(part_a(input, 20)?, 10605);
+        assert_eq!(part_b(input, 10_000)?, 2713310158);
+        Ok(())
+    }
+
+    #[test]
+    fn exact() -> Result<()> {
+        let input = include_str!("../sample.txt");
+        let exact = part_b_exact(input, 20)?;
+        assert_eq!(exact.business, 10197);
+        assert_eq!(exact.business, part_b(input, 20)?);
+        assert_eq!(
+            part_b_exact(input, 1)?,
+            Exact {
+                business: 24,
+                digits: 4
+            }
+        );
+
+        // division doesn't survive the modulo, and the check notices
+        let halving = input.replace("old * old", "old * old / 2");
+        let e = part_b_exact(&halving, 20).unwrap_err().to_string();
+        assert!(e.starts_with("Round "), "{e}");
+        Ok(())
+    }
+
+    #[test]
+    fn overflow() {
+        let cubes = "Monkey 0:
+  Starting items: 3
+  Operation: new = old * old * old
+  Test: divisible by 2
+    If true: throw to monkey 1
+    If false: throw to monkey 1
+
+Monkey 1:
+  Starting items:
+  Operation: new = old * 1000
+  Test: divisible by 3
+    If true: throw to monkey 0
+    If false: throw to monkey 0";
+        let e = part_a(cubes, 20).unwrap_err();
+        assert_eq!(
+            format!("{e:#}"),
+            "Round 3: monkey 0 doing new = old * old * old to 3000000000000: \
+             3000000000000 * 3000000000000 overflows an isize"
+        );
+        assert!(part_b_exact(cubes, 5).is_ok());
+    }
+
+    #[test]
+    fn operations() -> Result<()> {
+        let (monkeys, modulo) = parse_input(include_str!("../sample.txt"))?;
+        assert_eq!(modulo, 23 * 19 * 13 * 17);
+        assert_eq!(
+            monkeys[2].operation,
+            Expr::Binary(expr::Op::Mul, Box::new(Expr::Old), Box::new(Expr::Old))
+        );
+
+        // printing gives back what was parsed
+        for m in &monkeys {
+            assert_eq!(Monkey::parse(&format!("Monkey:\n{m}"))?, *m);
+        }
+
+        let odd = "Monkey 0:
+  Starting items: 10, 20
+  Operation: new = (old - 4) % 7 * 3
+  Test: divisible by 2
+    If true: throw to monkey 1
+    If false: throw to monkey 1
+
+Monkey 1:
+  Starting items:
+  Operation: new = old / 2
+  Test: divisible by 3
+    If true: throw to monkey 0
+    If false: throw to monkey 0";
+        let (monkeys, _) = parse_input(odd)?;
+        assert_eq!(monkeys[0].operation.eval(&20)?, 6);
+        assert!(monkeys[1].items.is_empty());
+        assert!(part_a(odd, 20).is_ok());
+        assert!(part_b(odd, 20).is_err());
+
+        assert!(parse_input(&odd.replace("old / 2", "old ^ 2")).is_err());
+        assert!(parse_input(&odd.replace("monkey 0", "monkey 5")).is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,141 @@
+//! Analysis of a whole crew's worth of cleanup assignments at once
+use crate::interval::{Interval, RangeSet};
+use std::io;
+
+/// What we know about one line of assignments
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GroupReport {
+    /// Indices of ranges that are entirely within some other elf's range
+    pub redundant: Vec<usize>,
+    /// Number of distinct sections anyone is cleaning
+    pub coverage: usize,
+    /// Sections that more than one elf is cleaning
+    pub overlap: RangeSet<i32>,
+    /// Sections nobody is cleaning, between the lowest and highest assigned
+    pub gaps: Vec<Interval<i32>>,
+}
+
+impl GroupReport {
+    pub fn any_redundant(&self) -> bool {
+        !self.redundant.is_empty()
+    }
+
+    pub fn any_overlap(&self) -> bool {
+        !self.overlap.is_empty()
+    }
+}
+
+/// Parse a line like `2-4,6-8,3-3` into its ranges
+pub fn parse_group(line: &str) -> io::Result<Vec<Interval<i32>>> {
+    line.split(',')
+        .map(|r| {
+            r.split_once('-')
+                .and_then(|(a, b)| {
+                    Some(Interval::new(
+                        a.trim().parse().ok()?,
+                        b.trim().parse().ok()?,
+                    ))
+                })
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Bad range {r:?} in line {line:?}"),
+                    )
+                })
+        })
+        .collect()
+}
+
+pub fn analyse(ranges: &[Interval<i32>]) -> GroupReport {
+    let redundant = ranges
+        .iter()
+        .enumerate()
+        .filter(|(i, x)| {
+            ranges
+                .iter()
+                .enumerate()
+                .any(|(j, y)| *i != j && y.contains(x))
+        })
+        .map(|(i, _)| i)
+        .collect();
+
+    let mut overlap = RangeSet::new();
+    for (i, x) in ranges.iter().enumerate() {
+        for y in &ranges[i + 1..] {
+            if let Some(both) = x.intersection(y) {
+                overlap.insert(both);
+            }
+        }
+    }
+
+    let covered: RangeSet<i32> = ranges.iter().copied().collect();
+
+    GroupReport {
+        redundant,
+        coverage: covered.len(),
+        overlap,
+        gaps: covered.gaps(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parsing() -> io::Result<()> {
+        assert_eq!(
+            parse_group("2-4,6-8")?,
+            [Interval::new(2, 4), Interval::new(6, 8)]
+        );
+        // any number of elves, endpoints in either order, stray spaces
+        assert_eq!(
+            parse_group("5-3, 7 - 7,1-9")?,
+            [
+                Interval::new(3, 5),
+                Interval::new(7, 7),
+                Interval::new(1, 9)
+            ]
+        );
+        for bad in ["", "2-4,", "2-4,6", "a-b", "2-4;6-8"] {
+            let e = parse_group(bad).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidData, "{bad:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn reports() -> io::Result<()> {
+        // from the puzzle: neither contained nor overlapping
+        let r = analyse(&parse_group("2-4,6-8")?);
+        assert!(!r.any_redundant() && !r.any_overlap());
+        assert_eq!(r.coverage, 6);
+        assert_eq!(r.gaps, [Interval::new(5, 5)]);
+
+        // 6-6 is inside both of the others, which share only section 6
+        let r = analyse(&parse_group("6-6,4-6,6-7")?);
+        assert_eq!(r.redundant, [0]);
+        assert_eq!(r.overlap.intervals(), [Interval::new(6, 6)]);
+        assert_eq!(r.coverage, 4);
+        assert!(r.gaps.is_empty());
+
+        // three elves, two identical ranges that each make the other redundant
+        let r = analyse(&parse_group("1-3,10-12,1-3,2-11")?);
+        assert_eq!(r.redundant, [0, 2]);
+        assert_eq!(
+            r.overlap.intervals(),
+            [Interval::new(1, 3), Interval::new(10, 11)]
+        );
+        assert_eq!(r.overlap.len(), 5);
+        assert_eq!(r.coverage, 12);
+
+        let r = analyse(&parse_group("1-2,5-5,9-10")?);
+        assert_eq!(r.gaps, [Interval::new(3, 4), Interval::new(6, 8)]);
+        assert!(!r.any_overlap());
+
+        let r = analyse(&parse_group("3-7")?);
+        assert!(!r.any_redundant() && r.gaps.is_empty());
+        assert_eq!(r.coverage, 5);
+        Ok(())
+    }
+}
@@ -0,0 +1,360 @@
+//! Closed intervals over discrete, ordered values (section IDs and the like)
+use std::fmt;
+
+/// Values with a well-defined successor and predecessor,
+/// so that `[1, 3]` and `[4, 6]` can be recognised as adjacent
+/// and `[1, 6] - [3, 4]` can be split into `[1, 2]` and `[5, 6]`
+pub trait Discrete: Copy + Ord {
+    /// None at the top of the type's range
+    fn succ(self) -> Option<Self>;
+    /// None at the bottom of the type's range
+    fn pred(self) -> Option<Self>;
+    /// Number of values in `start..=end`
+    fn count(start: Self, end: Self) -> usize;
+}
+
+macro_rules! impl_discrete {
+    ($($t:ty),*) => {
+        $(
+            impl Discrete for $t {
+                fn succ(self) -> Option<Self> {
+                    self.checked_add(1)
+                }
+                fn pred(self) -> Option<Self> {
+                    self.checked_sub(1)
+                }
+                fn count(start: Self, end: Self) -> usize {
+                    (end as i128 - start as i128 + 1).max(0) as usize
+                }
+            }
+        )*
+    };
+}
+
+impl_discrete!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+/// The closed interval `start..=end`; by construction `start <= end`
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Interval<T> {
+    start: T,
+    end: T,
+}
+
+impl<T: Discrete> Interval<T> {
+    /// The endpoints may be given in either order
+    pub fn new(a: T, b: T) -> Self {
+        Interval {
+            start: a.min(b),
+            end: a.max(b),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn start(&self) -> T {
+        self.start
+    }
+
+    #[allow(dead_code)]
+    pub fn end(&self) -> T {
+        self.end
+    }
+
+    /// Number of values in the interval (never zero)
+    pub fn len(&self) -> usize {
+        T::count(self.start, self.end)
+    }
+
+    #[allow(dead_code)]
+    pub fn contains_point(&self, x: T) -> bool {
+        self.start <= x && x <= self.end
+    }
+
+    /// Is `other` entirely within `self`?
+    pub fn contains(&self, other: &Self) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    /// Do the two intervals share at least one value?
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+
+    /// Do the two intervals overlap or sit directly next to each other?
+    pub fn touches(&self, other: &Self) -> bool {
+        self.overlaps(other)
+            || self.end.succ() == Some(other.start)
+            || other.end.succ() == Some(self.start)
+    }
+
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        if self.overlaps(other) {
+            Some(Interval {
+                start: self.start.max(other.start),
+                end: self.end.min(other.end),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The union, if it is itself a single interval
+    /// (i.e. the two overlap or are adjacent)
+    pub fn union(&self, other: &Self) -> Option<Self> {
+        if self.touches(other) {
+            Some(Interval {
+                start: self.start.min(other.start),
+                end: self.end.max(other.end),
+            })
+        } else {
+            None
+        }
+    }
+
+    #[allow(dead_code)]
+    /// Everything in `self` but not in `other`: zero, one or two intervals
+    pub fn difference(&self, other: &Self) -> Vec<Self> {
+        if !self.overlaps(other) {
+            return vec![*self];
+        }
+        // a piece sticks out past `other` only if `other` stops short of
+        // the type's bounds, so pred and succ are always there when needed
+        let mut out = Vec::with_capacity(2);
+        if let Some(end) = other.start.pred().filter(|_| self.start < other.start) {
+            out.push(Interval {
+                start: self.start,
+                end,
+            });
+        }
+        if let Some(start) = other.end.succ().filter(|_| other.end < self.end) {
+            out.push(Interval {
+                start,
+                end: self.end,
+            });
+        }
+        out
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Interval<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+/// A set of values stored as sorted, disjoint, non-adjacent intervals
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RangeSet<T> {
+    intervals: Vec<Interval<T>>,
+}
+
+impl<T: Discrete> RangeSet<T> {
+    pub fn new() -> Self {
+        RangeSet { intervals: vec![] }
+    }
+
+    /// Add an interval, coalescing it with any it touches
+    pub fn insert(&mut self, iv: Interval<T>) {
+        let mut merged = iv;
+        self.intervals.retain(|x| match merged.union(x) {
+            Some(u) => {
+                merged = u;
+                false
+            }
+            None => true,
+        });
+        let pos = self.intervals.partition_point(|x| x.start < merged.start);
+        self.intervals.insert(pos, merged);
+    }
+
+    #[allow(dead_code)]
+    /// Remove every value of `iv` from the set
+    pub fn remove(&mut self, iv: &Interval<T>) {
+        self.intervals = self
+            .intervals
+            .iter()
+            .flat_map(|x| x.difference(iv))
+            .collect();
+    }
+
+    #[allow(dead_code)]
+    pub fn intervals(&self) -> &[Interval<T>] {
+        &self.intervals
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// Total number of values covered
+    pub fn len(&self) -> usize {
+        self.intervals.iter().map(Interval::len).sum()
+    }
+
+    #[allow(dead_code)]
+    pub fn contains_point(&self, x: T) -> bool {
+        // intervals are sorted, so find the last one starting at or before x
+        let pos = self.intervals.partition_point(|iv| iv.start <= x);
+        pos > 0 && self.intervals[pos - 1].contains_point(x)
+    }
+
+    #[allow(dead_code)]
+    /// Smallest single interval covering the whole set
+    pub fn span(&self) -> Option<Interval<T>> {
+        Some(Interval {
+            start: self.intervals.first()?.start,
+            end: self.intervals.last()?.end,
+        })
+    }
+
+    /// The holes between consecutive intervals
+    pub fn gaps(&self) -> Vec<Interval<T>> {
+        // consecutive intervals never touch, so there's room between them
+        self.intervals
+            .windows(2)
+            .filter_map(|w| {
+                Some(Interval {
+                    start: w[0].end.succ()?,
+                    end: w[1].start.pred()?,
+                })
+            })
+            .collect()
+    }
+}
+
+impl<T: Discrete> FromIterator<Interval<T>> for RangeSet<T> {
+    fn from_iter<I: IntoIterator<Item = Interval<T>>>(iter: I) -> Self {
+        let mut out = RangeSet::new();
+        for iv in iter {
+            out.insert(iv);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    /// Every interval with endpoints in `1..=7`
+    fn all_intervals() -> Vec<Interval<i32>> {
+        let mut out = vec![];
+        for a in 1..=7 {
+            for b in a..=7 {
+                out.push(Interval::new(a, b));
+            }
+        }
+        out
+    }
+
+    fn points(iv: &Interval<i32>) -> BTreeSet<i32> {
+        (iv.start()..=iv.end()).collect()
+    }
+
+    #[test]
+    fn matches_naive_expressions() {
+        for x in all_intervals() {
+            for y in all_intervals() {
+                let (a, b, c, d) = (x.start(), x.end(), y.start(), y.end());
+                assert_eq!(
+                    x.contains(&y) || y.contains(&x),
+                    (a <= c && b >= d) || (c <= a && d >= b),
+                    "{x} {y}"
+                );
+                // the original part B test
+                assert_eq!(
+                    x.overlaps(&y),
+                    (b <= d || a <= c) && c <= b || (a <= d && d <= b) || (c <= a && a <= d),
+                    "{x} {y}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn set_operations_match_point_sets() {
+        for x in all_intervals() {
+            for y in all_intervals() {
+                let (px, py) = (points(&x), points(&y));
+
+                let inter: BTreeSet<i32> = x.intersection(&y).iter().flat_map(points).collect();
+                assert_eq!(inter, &px & &py, "{x} & {y}");
+
+                let diff: BTreeSet<i32> = x.difference(&y).iter().flat_map(points).collect();
+                assert_eq!(diff, &px - &py, "{x} - {y}");
+
+                let both = &px | &py;
+                match x.union(&y) {
+                    Some(u) => assert_eq!(points(&u), both, "{x} | {y}"),
+                    None => {
+                        let (lo, hi) = (both.first().unwrap(), both.last().unwrap());
+                        assert!(both.len() < (hi - lo + 1) as usize, "{x} | {y}")
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn range_set_coalesces() {
+        let ivs = all_intervals();
+        // a deterministic spread of three-interval combinations
+        for (i, x) in ivs.iter().enumerate() {
+            for y in ivs.iter().skip(i % 5).step_by(3) {
+                for z in ivs.iter().skip(i % 7).step_by(5) {
+                    let set: RangeSet<i32> = [*x, *y, *z].into_iter().collect();
+                    let naive: BTreeSet<i32> = [x, y, z].into_iter().flat_map(points).collect();
+                    let covered: BTreeSet<i32> = set.intervals().iter().flat_map(points).collect();
+                    assert_eq!(covered, naive);
+                    assert_eq!(set.len(), naive.len());
+                    assert!(set.gaps().iter().all(|g| g.len() > 0));
+                    let span = set.span().unwrap();
+                    assert_eq!(span.start(), *naive.first().unwrap());
+                    assert_eq!(span.end(), *naive.last().unwrap());
+                    for w in set.intervals().windows(2) {
+                        assert!(!w[0].touches(&w[1]));
+                    }
+                    for p in 0..=8 {
+                        assert_eq!(set.contains_point(p), naive.contains(&p));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn type_bounds() {
+        let top = Interval::new(250u8, u8::MAX);
+        let below = Interval::new(240u8, 249);
+        assert!(top.touches(&below) && below.touches(&top));
+        assert_eq!(top.union(&below), Some(Interval::new(240, 255)));
+        assert_eq!(
+            Interval::new(0u8, u8::MAX).difference(&top),
+            vec![Interval::new(0, 249)]
+        );
+        assert_eq!(
+            top.difference(&Interval::new(0, 252)),
+            vec![Interval::new(253, 255)]
+        );
+        assert!(top.difference(&top).is_empty());
+        assert_eq!(top.len(), 6);
+
+        let bottom = Interval::new(i32::MIN, i32::MIN + 2);
+        let set: RangeSet<i32> = [bottom, Interval::new(0, 1), Interval::new(i32::MIN + 3, -5)]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            set.intervals(),
+            [Interval::new(i32::MIN, -5), Interval::new(0, 1)]
+        );
+        assert_eq!(set.gaps(), [Interval::new(-4, -1)]);
+        assert_eq!(
+            Interval::new(i32::MIN, 0).difference(&bottom),
+            vec![Interval::new(i32::MIN + 3, 0)]
+        );
+        let mut full: RangeSet<u8> = [Interval::new(0, u8::MAX)].into_iter().collect();
+        full.remove(&Interval::new(0, 0));
+        full.remove(&Interval::new(u8::MAX, u8::MAX));
+        assert_eq!(full.intervals(), [Interval::new(1, 254)]);
+    }
+}
@@ -1,29 +1,45 @@
+mod crew;
+mod interval;
+
 use std::io;
 
 fn main() -> io::Result<()> {
+    // `report` describes every group on stderr as well
+    let report_groups = match std::env::args().nth(1).as_deref() {
+        None => false,
+        Some("report") => true,
+        Some(arg) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unknown argument {arg:?}: try report"),
+            ))
+        }
+    };
+
     let mut total_a = 0;
     let mut total_b = 0;
 
     let lines = io::stdin().lines();
-    for line in lines {
-        let nums: Vec<i32> = line?
-            .split(',')
-            .flat_map(|x| x.split('-'))
-            .filter_map(|x| x.parse::<i32>().ok())
-            .collect();
-        let a = nums[0];
-        let b = nums[1];
-        let c = nums[2];
-        let d = nums[3];
-
-        // Part A: range AB or range CD
-        // completely contained within the other
-        if (a <= c && b >= d) || (c <= a && d >= b) {
+    for (i, line) in lines.enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let ranges = crew::parse_group(&line)?;
+        let report = crew::analyse(&ranges);
+
+        if report_groups {
+            describe(i + 1, &ranges, &report);
+        }
+
+        // Part A: some range is completely
+        // contained within another
+        if report.any_redundant() {
             total_a += 1;
         }
 
-        // Part B: range AB and CD overlap
-        if (b <= d || a <= c) && c <= b || (a <= d && d <= b) || (c <= a && a <= d) {
+        // Part B: some pair of ranges overlap
+        if report.any_overlap() {
             total_b += 1
         }
     }
@@ -33,3 +49,28 @@ fn main() -> io::Result<()> {
 
     Ok(())
 }
+
+/// One line on stderr summing up a group
+fn describe(group: usize, ranges: &[interval::Interval<i32>], report: &crew::GroupReport) {
+    eprint!(
+        "Group {group}: {} elves, {} sections covered",
+        ranges.len(),
+        report.coverage
+    );
+    if report.any_redundant() {
+        let which: Vec<String> = report
+            .redundant
+            .iter()
+            .map(|r| ranges[*r].to_string())
+            .collect();
+        eprint!(", redundant: {}", which.join(","));
+    }
+    if report.any_overlap() {
+        eprint!(", {} sections shared", report.overlap.len());
+    }
+    if !report.gaps.is_empty() {
+        let gaps: Vec<String> = report.gaps.iter().map(|g| g.to_string()).collect();
+        eprint!(", gaps: {}", gaps.join(","));
+    }
+    eprintln!();
+}
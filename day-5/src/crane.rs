@@ -0,0 +1,108 @@
+//! Cranes and a step-by-step simulation of the rearrangement
+use crate::drawing::{Move, Stacks};
+use anyhow::{ensure, Result};
+
+/// A crane moves crates between stacks, some number at a time
+pub trait Crane {
+    /// Most crates lifted in a single grab; these keep their order
+    fn capacity(&self) -> usize;
+
+    /// Carry out one instruction, grab by grab
+    ///
+    /// The move is checked first, so an illegal move leaves `stacks` untouched.
+    fn apply(&self, stacks: &mut Stacks, mv: &Move) -> Result<()> {
+        mv.validate(stacks)?;
+        let capacity = self.capacity();
+        ensure!(capacity > 0, "A crane must lift at least one crate");
+
+        let mut remaining = mv.qty;
+        while remaining > 0 {
+            let n = remaining.min(capacity);
+            let from = stacks.entry(mv.from).or_default();
+            let mut grab = from.split_off(from.len() - n);
+            stacks.entry(mv.to).or_default().append(&mut grab);
+            remaining -= n;
+        }
+        Ok(())
+    }
+}
+
+/// Part A: one crate at a time
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn capacity(&self) -> usize {
+        1
+    }
+}
+
+/// Part B: everything in one go
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CrateMover9001;
+
+impl Crane for CrateMover9001 {
+    fn capacity(&self) -> usize {
+        usize::MAX
+    }
+}
+
+#[cfg(test)]
+/// Somewhere in between: at most K crates per grab
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LimitedCrane(pub usize);
+
+#[cfg(test)]
+impl Crane for LimitedCrane {
+    fn capacity(&self) -> usize {
+        self.0
+    }
+}
+
+/// Runs the moves one at a time, yielding each move along with the state after it
+///
+/// Stops after the first illegal move, which is yielded as an error.
+pub struct Simulator<'a> {
+    crane: &'a dyn Crane,
+    state: Stacks,
+    moves: std::slice::Iter<'a, Move>,
+    failed: bool,
+}
+
+impl<'a> Simulator<'a> {
+    pub fn new(crane: &'a dyn Crane, start: Stacks, moves: &'a [Move]) -> Self {
+        Simulator {
+            crane,
+            state: start,
+            moves: moves.iter(),
+            failed: false,
+        }
+    }
+
+    /// Run to the end and return the final arrangement
+    pub fn finish(self) -> Result<Stacks> {
+        let mut last = self.state.clone();
+        for step in self {
+            last = step?.1;
+        }
+        Ok(last)
+    }
+}
+
+impl Iterator for Simulator<'_> {
+    type Item = Result<(Move, Stacks)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let mv = self.moves.next()?;
+        match self.crane.apply(&mut self.state, mv) {
+            Ok(()) => Some(Ok((*mv, self.state.clone()))),
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e.context(format!("Illegal move: {mv}"))))
+            }
+        }
+    }
+}
@@ -0,0 +1,184 @@
+//! Reading and writing the crate-stack drawings and the move list
+use anyhow::{bail, ensure, Context, Result};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Display;
+
+/// Stacks by label; the back of each deque is the top of the stack
+pub type Stacks = BTreeMap<usize, VecDeque<String>>;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Move {
+    pub qty: usize,
+    pub from: usize,
+    pub to: usize,
+}
+
+impl std::fmt::Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "move {} from {} to {}", self.qty, self.from, self.to)
+    }
+}
+
+impl Move {
+    /// Parse `move N from A to B`
+    pub fn parse(line: &str) -> Result<Self> {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if let ["move", qty, "from", from, "to", to] = tokens[..] {
+            Ok(Move {
+                qty: qty.parse().context(format!("Bad quantity in {line:?}"))?,
+                from: from.parse().context(format!("Bad origin in {line:?}"))?,
+                to: to.parse().context(format!("Bad destination in {line:?}"))?,
+            })
+        } else {
+            bail!("Not a move instruction: {line:?}")
+        }
+    }
+
+    /// Check that this move makes sense for the current state of the stacks
+    pub fn validate<T>(&self, stacks: &BTreeMap<usize, VecDeque<T>>) -> Result<()> {
+        let Some(from) = stacks.get(&self.from) else {
+            bail!("{self}: there is no stack {}", self.from)
+        };
+        ensure!(
+            stacks.contains_key(&self.to),
+            "{self}: there is no stack {}",
+            self.to
+        );
+        ensure!(
+            from.len() >= self.qty,
+            "{self}: stack {} only holds {} crates",
+            self.from,
+            from.len()
+        );
+        Ok(())
+    }
+}
+
+/// Split the whole puzzle input into the starting stacks and the moves
+///
+/// The drawing is everything before the first blank line,
+/// and its last line holds the stack labels
+pub fn parse_input(input: &str) -> Result<(Stacks, Vec<Move>)> {
+    let mut lines = input.lines();
+    let drawing: Vec<&str> = lines
+        .by_ref()
+        .take_while(|l| !l.trim().is_empty())
+        .collect();
+    let stacks = parse_drawing(&drawing)?;
+    let moves = lines
+        .filter(|l| !l.trim().is_empty())
+        .map(Move::parse)
+        .collect::<Result<Vec<Move>>>()?;
+    Ok((stacks, moves))
+}
+
+/// Character spans of the whitespace-separated tokens in a line
+fn token_spans(line: &str) -> Vec<(usize, usize, &str)> {
+    let mut out = vec![];
+    let mut start = None;
+    for (i, c) in line.char_indices().chain([(line.len(), ' ')]) {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some(i),
+            (Some(s), true) => {
+                out.push((s, i - 1, &line[s..i]));
+                start = None;
+            }
+            _ => (),
+        }
+    }
+    out
+}
+
+/// Parse the stack drawing, label line last
+///
+/// Columns are located from the label line rather than assumed,
+/// so a crate belongs to whichever label its `[...]` sits above.
+pub fn parse_drawing(lines: &[&str]) -> Result<Stacks> {
+    let Some((labels, rows)) = lines.split_last() else {
+        bail!("Empty stack drawing")
+    };
+
+    let mut columns = vec![];
+    let mut stacks = Stacks::new();
+    for (l, r, tok) in token_spans(labels) {
+        let label: usize = tok
+            .parse()
+            .context(format!("Expected a stack label, got {tok:?} in {labels:?}"))?;
+        ensure!(
+            stacks.insert(label, VecDeque::new()).is_none(),
+            "Stack {label} is labelled twice"
+        );
+        columns.push((l, r, label));
+    }
+
+    // Bottom row first, so that each crate lands on the one below it
+    for (level, row) in rows.iter().rev().enumerate() {
+        let mut rest = *row;
+        let mut offset = 0;
+        while let Some(open) = rest.find('[') {
+            let Some(close) = rest[open..].find(']').map(|c| c + open) else {
+                bail!("Unclosed crate in {row:?}")
+            };
+            let name = &rest[open + 1..close];
+            ensure!(!name.is_empty(), "Unnamed crate in {row:?}");
+            let (l, r) = (offset + open, offset + close);
+
+            let mut hits = columns.iter().filter(|(a, b, _)| *a <= r && l <= *b);
+            let (Some((_, _, label)), None) = (hits.next(), hits.next()) else {
+                bail!("Crate [{name}] in {row:?} does not sit above exactly one label")
+            };
+
+            let stack = stacks.entry(*label).or_default();
+            ensure!(
+                stack.len() == level,
+                "Crate [{name}] in {row:?} is floating above stack {label}"
+            );
+            stack.push_back(name.to_string());
+
+            offset += close + 1;
+            rest = &row[offset..];
+        }
+    }
+
+    Ok(stacks)
+}
+
+/// Draw the stacks in the same format we read them
+pub fn render<T: Display>(stacks: &BTreeMap<usize, VecDeque<T>>) -> String {
+    let names: BTreeMap<usize, Vec<String>> = stacks
+        .iter()
+        .map(|(k, v)| (*k, v.iter().map(|c| format!("[{c}]")).collect()))
+        .collect();
+
+    let width = names
+        .iter()
+        .flat_map(|(k, v)| v.iter().map(|c| c.len()).chain([k.to_string().len()]))
+        .max()
+        .unwrap_or(0);
+    let height = stacks.values().map(VecDeque::len).max().unwrap_or(0);
+
+    let mut out = String::new();
+    for level in (0..height).rev() {
+        let row: Vec<String> = names
+            .values()
+            .map(|v| format!("{:<width$}", v.get(level).map_or("", |c| c)))
+            .collect();
+        out += row.join(" ").trim_end();
+        out.push('\n');
+    }
+    let labels: Vec<String> = names
+        .keys()
+        .map(|k| format!("{:^width$}", k.to_string()))
+        .collect();
+    out += &labels.join(" ");
+    out.push('\n');
+    out
+}
+
+/// The crate on top of each stack, in label order
+pub fn tops<T: Display>(stacks: &BTreeMap<usize, VecDeque<T>>) -> String {
+    stacks
+        .values()
+        .map(|v| v.back().map_or(String::from(" "), |c| c.to_string()))
+        .collect()
+}
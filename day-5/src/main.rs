@@ -1,69 +1,225 @@
-use std::{
-    collections::{BTreeMap, VecDeque},
-    io,
-};
+mod crane;
+mod drawing;
+mod planner;
+
+use anyhow::Result;
+use crane::{CrateMover9000, CrateMover9001, Simulator};
+use drawing::{parse_input, render, tops};
+use planner::{parse_target, Planner};
+use std::io::{self, Read};
 
 /// Stack rearrangement
-fn main() -> io::Result<()> {
-    let lines: Vec<String> = io::stdin().lines().filter_map(|x| x.ok()).collect();
+///
+/// Given a target (e.g. `CMZ`, or `AB,,C` for longer crate names) as an
+/// argument, instead print moves that get the starting drawing there with
+/// each crane
+fn main() -> Result<()> {
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
     // step 1: garner input
     // three line typologies:
     // * stack drawing
     // * stack labels
     // [blank line]
     // * move instructions
+    let (stacks, moves) = parse_input(&input)?;
+
+    if let Some(target) = std::env::args().nth(1) {
+        let target = parse_target(&target);
+        print!("{}", render(&stacks));
+        for (name, crane) in [
+            ("CrateMover 9000", &CrateMover9000 as &dyn crane::Crane),
+            ("CrateMover 9001", &CrateMover9001),
+        ] {
+            let plan = Planner::default().plan(crane, &stacks, &target)?;
+            println!("\n{name}: {} moves", plan.len());
+            for mv in plan {
+                println!("{mv}");
+            }
+        }
+        return Ok(());
+    }
+
+    // Part A: one crate at a time
+    let stacks_a = Simulator::new(&CrateMover9000, stacks.clone(), &moves).finish()?;
+
+    // Part B: many crates at once
+    let stacks_b = Simulator::new(&CrateMover9001, stacks, &moves).finish()?;
+
+    // need top (last) from each
+    eprintln!("Part A: {}", tops(&stacks_a));
+    eprintln!("Part B: {}", tops(&stacks_b));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::crane::*;
+    use super::drawing::*;
+    use super::planner::*;
+    use anyhow::Result;
+
+    const SAMPLE: &str =
+        "    [D]    \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 \n\nmove 1 from 2 to 1\n";
+
+    #[test]
+    fn sample_round_trip() -> Result<()> {
+        let (stacks, moves) = parse_input(SAMPLE)?;
+        assert_eq!(tops(&stacks), "NDP");
+        assert_eq!(moves[0].to_string(), "move 1 from 2 to 1");
+        let drawing = render(&stacks);
+        let lines: Vec<&str> = drawing.lines().collect();
+        assert_eq!(parse_drawing(&lines)?, stacks);
+        Ok(())
+    }
+
+    #[test]
+    fn wide_labels_and_names() -> Result<()> {
+        let drawing = [
+            "[AB]                     [KL]",
+            "[CD] [EF] [GH] [IJ] [XY] [MN] [OP] [QR] [ST] [UV]",
+            " 1    2    3    4    5    6    7    8    9    10 ",
+        ];
+        let stacks = parse_drawing(&drawing)?;
+        assert_eq!(stacks.len(), 10);
+        assert_eq!(tops(&stacks), "ABEFGHIJXYKLOPQRSTUV");
+        let rendered = render(&stacks);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(parse_drawing(&lines)?, stacks);
+        Ok(())
+    }
 
-    let mut stacks_a: BTreeMap<usize, VecDeque<String>> = BTreeMap::new();
-    let mut stacks_b: BTreeMap<usize, VecDeque<String>> = BTreeMap::new();
-
-    for line in lines {
-        // A somewhat fragile parse but it will do
-        if line.contains('[') {
-            for (i, v) in line.chars().enumerate() {
-                if i % 4 == 1 && !v.is_whitespace() {
-                    stacks_a
-                        .entry(i / 4 + 1)
-                        .or_default()
-                        .push_front(String::from(v));
-                    stacks_b
-                        .entry(i / 4 + 1)
-                        .or_default()
-                        .push_front(String::from(v));
+    #[test]
+    fn rejects_bad_moves() -> Result<()> {
+        let (stacks, _) = parse_input(SAMPLE)?;
+        assert!(Move::parse("move 4 from 3 to 1")?
+            .validate(&stacks)
+            .is_err());
+        assert!(Move::parse("move 1 from 4 to 1")?
+            .validate(&stacks)
+            .is_err());
+        assert!(Move::parse("move one from 1 to 2").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn crane_models() -> Result<()> {
+        let (stacks, _) = parse_input(SAMPLE)?;
+        // stack 1 becomes Z N D (bottom to top), then all three go onto P
+        let moves = [
+            Move::parse("move 1 from 2 to 1")?,
+            Move::parse("move 3 from 1 to 3")?,
+        ];
+        for (crane, expected) in [
+            (&CrateMover9000 as &dyn Crane, ["P", "D", "N", "Z"]),
+            (&CrateMover9001, ["P", "Z", "N", "D"]),
+            (&LimitedCrane(2), ["P", "N", "D", "Z"]),
+        ] {
+            let end = Simulator::new(crane, stacks.clone(), &moves).finish()?;
+            assert_eq!(end[&3], expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn illegal_move_is_an_error() -> Result<()> {
+        let (stacks, _) = parse_input(SAMPLE)?;
+        let moves = [
+            Move::parse("move 1 from 2 to 1")?,
+            Move::parse("move 5 from 3 to 1")?,
+        ];
+        let steps: Vec<_> = Simulator::new(&CrateMover9001, stacks, &moves).collect();
+        assert_eq!(steps.len(), 2);
+        assert!(steps[0].is_ok() && steps[1].is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn planner_reaches_target() -> Result<()> {
+        let (stacks, _) = parse_input(SAMPLE)?;
+        for crane in [&CrateMover9000 as &dyn Crane, &CrateMover9001] {
+            for target in ["CMZ", "ZNP", "DC ", "M P", "  Z"] {
+                let shortest = shortest(crane, &stacks, target);
+                for exact in [false, true] {
+                    let planner = Planner {
+                        exact,
+                        ..Planner::default()
+                    };
+                    let plan = planner.plan(crane, &stacks, &parse_target(target))?;
+                    let end = Simulator::new(crane, stacks.clone(), &plan).finish()?;
+                    assert_eq!(tops(&end), target);
+                    if exact {
+                        assert_eq!(plan.len(), shortest, "{target}");
+                    }
                 }
             }
-        } else if line.contains("move") {
-            let tokens: Vec<&str> = line.split_whitespace().collect();
-
-            let qty: usize = tokens[1].parse().unwrap();
-            let orig: usize = tokens[3].parse().unwrap();
-            let dest: usize = tokens[5].parse().unwrap();
-
-            // Part A
-            // recursively reorganise
-            for _ in 0..qty {
-                let v = stacks_a.get_mut(&orig).unwrap().pop_back().unwrap();
-                stacks_a.get_mut(&dest).unwrap().push_back(v);
+            for bad in ["XNP", "   ", "CM"] {
+                assert!(Planner::default()
+                    .plan(crane, &stacks, &parse_target(bad))
+                    .is_err());
             }
-
-            // Part B
-            let from = stacks_b.get_mut(&orig).unwrap();
-            let mut moved = from.split_off(from.len() - qty);
-            stacks_b.get_mut(&dest).unwrap().append(&mut moved);
         }
+        Ok(())
     }
 
-    // need top (last) from each
-    eprint!("Part A: ");
-    for (_, v) in stacks_a {
-        eprint!("{}", v.back().unwrap_or(&String::from(" ")));
+    /// Length of the shortest plan, by breadth-first search
+    fn shortest(crane: &dyn Crane, start: &Stacks, target: &str) -> usize {
+        let mut seen = std::collections::HashSet::from([start.clone()]);
+        let mut layer = vec![start.clone()];
+        for depth in 0.. {
+            if layer.iter().any(|s| tops(s) == target) {
+                return depth;
+            }
+            let mut next = vec![];
+            for s in &layer {
+                for (&from, v) in s {
+                    for &to in s.keys().filter(|&&to| to != from) {
+                        for qty in 1..=v.len() {
+                            let mut t = s.clone();
+                            crane.apply(&mut t, &Move { qty, from, to }).unwrap();
+                            if seen.insert(t.clone()) {
+                                next.push(t);
+                            }
+                        }
+                    }
+                }
+            }
+            layer = next;
+        }
+        unreachable!()
     }
-    eprintln!();
 
-    eprint!("Part B: ");
-    for (_, v) in stacks_b {
-        eprint!("{}", v.back().unwrap_or(&String::from(" ")));
+    #[test]
+    fn planner_handles_long_names() -> Result<()> {
+        let drawing = ["[AB]          ", "[CD] [EF] [GH]", " 1    2    3  "];
+        let stacks = parse_drawing(&drawing)?;
+        let target = parse_target("GH,AB,");
+        assert_eq!(target, [Some("GH".into()), Some("AB".into()), None]);
+        let planner = Planner {
+            exact: true,
+            ..Planner::default()
+        };
+        let plan = planner.plan(&CrateMover9000, &stacks, &target)?;
+        let end = Simulator::new(&CrateMover9000, stacks.clone(), &plan).finish()?;
+        assert_eq!(tops(&end), "GHAB ");
+        assert_eq!(plan.len(), shortest(&CrateMover9000, &stacks, "GHAB "));
+        Ok(())
     }
-    eprintln!();
 
-    Ok(())
+    #[test]
+    fn planner_handles_the_real_drawing() -> Result<()> {
+        let (stacks, _) = parse_input(include_str!("../input.txt"))?;
+        assert_eq!(tops(&stacks), "FPLRBZCCV");
+        for crane in [&CrateMover9000 as &dyn Crane, &CrateMover9001] {
+            // swapping two tops, the part A answer, and emptying a stack
+            for (target, most) in [("PFLRBZCCV", 4), ("CVCWCRTVQ", 18), ("FPLRBZC V", 3)] {
+                let plan = Planner::default().plan(crane, &stacks, &parse_target(target))?;
+                let end = Simulator::new(crane, stacks.clone(), &plan).finish()?;
+                assert_eq!(tops(&end), target);
+                assert!(plan.len() <= most, "{target}: {} moves", plan.len());
+            }
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,360 @@
+//! Working backwards: find moves that leave a given crate on top of each stack
+use crate::crane::{Crane, Simulator};
+use crate::drawing::{Move, Stacks};
+use anyhow::{bail, ensure, Result};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::rc::Rc;
+
+/// Finds moves that get the right crates on top
+///
+/// By default each stack is fixed in turn, digging out a crate for it and
+/// parking whatever was on top somewhere that doesn't matter yet. That gives
+/// a short plan quickly for drawings of any size, though not the shortest.
+/// `exact` instead runs a weighted A* search over arrangements, which tries
+/// every legal move of every quantity and so is only fit for puzzle-sized
+/// drawings; it's also what's used when digging gets stuck.
+pub struct Planner {
+    /// Search rather than dig
+    pub exact: bool,
+    /// Give up searching after looking at this many arrangements
+    pub max_states: usize,
+    /// 1 finds a shortest plan, as states are only settled once taken off
+    /// the queue and the estimate never overshoots; higher values trade
+    /// plan length for search speed
+    pub greed: usize,
+}
+
+impl Default for Planner {
+    fn default() -> Self {
+        Planner {
+            exact: false,
+            max_states: 200_000,
+            greed: 1,
+        }
+    }
+}
+
+/// Stacks whose top crate isn't what we want yet
+fn wrong_tops(stacks: &Stacks, target: &[Option<String>]) -> usize {
+    stacks
+        .values()
+        .zip(target)
+        .filter(|(v, t)| v.back() != t.as_ref())
+        .count()
+}
+
+/// Parse a target for [`Planner::plan`]
+///
+/// Either one character per stack in label order, with a space meaning the
+/// stack should end up empty (`"DC "`), or, for longer crate names, the
+/// names separated by commas, with nothing between them for an empty
+/// stack (`"DD,,Z"`).
+pub fn parse_target(target: &str) -> Vec<Option<String>> {
+    if target.contains(',') {
+        target
+            .split(',')
+            .map(|t| t.trim())
+            .map(|t| (!t.is_empty()).then(|| t.to_string()))
+            .collect()
+    } else {
+        target
+            .chars()
+            .map(|c| (c != ' ').then(|| c.to_string()))
+            .collect()
+    }
+}
+
+/// An arrangement reached during the search
+struct Node {
+    stacks: Rc<Stacks>,
+    /// Where we got here from, and how
+    parent: Option<(usize, Move)>,
+    /// Moves along the cheapest known way here
+    g: usize,
+    /// Expanded already, so `g` is final
+    closed: bool,
+}
+
+impl Planner {
+    /// Find a short list of moves after which the top of each stack,
+    /// in label order, is the crate named in `target`, or nothing
+    pub fn plan(
+        &self,
+        crane: &dyn Crane,
+        start: &Stacks,
+        target: &[Option<String>],
+    ) -> Result<Vec<Move>> {
+        ensure!(
+            target.len() == start.len(),
+            "Target names {} stacks but the drawing has {}",
+            target.len(),
+            start.len()
+        );
+
+        // cheap checks for the obviously impossible
+        let mut available: HashMap<&String, usize> = HashMap::new();
+        for c in start.values().flatten() {
+            *available.entry(c).or_default() += 1;
+        }
+        for t in target.iter().flatten() {
+            match available.get_mut(t) {
+                Some(n) if *n > 0 => *n -= 1,
+                _ => bail!("Not enough [{t}] crates to go around"),
+            }
+        }
+        ensure!(
+            target.iter().any(Option::is_some) || start.values().all(|v| v.is_empty()),
+            "Every stack is meant to be empty but there are crates to put somewhere"
+        );
+
+        let dug = if self.exact {
+            None
+        } else {
+            dig(crane, start, target)?
+        };
+        let moves = match dug {
+            Some(moves) => moves,
+            None => self.search(crane, start, target)?,
+        };
+
+        // belt and braces: replay it forwards
+        let end = Simulator::new(crane, start.clone(), &moves).finish()?;
+        ensure!(
+            wrong_tops(&end, target) == 0,
+            "Plan does not replay correctly"
+        );
+        Ok(moves)
+    }
+
+    /// Weighted A* over every arrangement reachable from `start`
+    fn search(
+        &self,
+        crane: &dyn Crane,
+        start: &Stacks,
+        target: &[Option<String>],
+    ) -> Result<Vec<Move>> {
+        let labels: Vec<usize> = start.keys().copied().collect();
+        // each move changes at most two tops, so this never overestimates
+        let h = |stacks: &Stacks| wrong_tops(stacks, target).div_ceil(2);
+
+        // every arrangement seen so far, each stored once and found by its
+        // index from the queue or by its contents from `index`
+        let first = Rc::new(start.clone());
+        let mut nodes = vec![Node {
+            stacks: first.clone(),
+            parent: None,
+            g: 0,
+            closed: false,
+        }];
+        let mut index = HashMap::from([(first, 0)]);
+        let mut queue = BinaryHeap::from([Reverse((0, 0))]);
+
+        while let Some(Reverse((_, id))) = queue.pop() {
+            // a cheaper way here was found after this entry was queued
+            if nodes[id].closed {
+                continue;
+            }
+            nodes[id].closed = true;
+
+            let current = nodes[id].stacks.clone();
+            if wrong_tops(&current, target) == 0 {
+                let mut moves = vec![];
+                let mut cur = id;
+                while let Some((parent, mv)) = nodes[cur].parent {
+                    moves.push(mv);
+                    cur = parent;
+                }
+                moves.reverse();
+                return Ok(moves);
+            }
+            if nodes.len() > self.max_states {
+                bail!(
+                    "No plan found within {} arrangements; try a simpler target",
+                    self.max_states
+                );
+            }
+
+            let g = nodes[id].g + 1;
+            for &from in &labels {
+                for &to in &labels {
+                    if from == to {
+                        continue;
+                    }
+                    for qty in 1..=current[&from].len() {
+                        let mv = Move { qty, from, to };
+                        let mut next = (*current).clone();
+                        crane.apply(&mut next, &mv)?;
+                        let f = g + self.greed * h(&next);
+                        match index.get(&next) {
+                            Some(&seen) => {
+                                let node = &mut nodes[seen];
+                                if node.closed || node.g <= g {
+                                    continue;
+                                }
+                                node.g = g;
+                                node.parent = Some((id, mv));
+                                queue.push(Reverse((f, seen)));
+                            }
+                            None => {
+                                let next = Rc::new(next);
+                                index.insert(next.clone(), nodes.len());
+                                queue.push(Reverse((f, nodes.len())));
+                                nodes.push(Node {
+                                    stacks: next,
+                                    parent: Some((id, mv)),
+                                    g,
+                                    closed: false,
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        bail!("No sequence of moves reaches that arrangement")
+    }
+}
+
+/// Fix one stack at a time, or None if that gets stuck
+///
+/// A stack is settled while it has the right crate on top. For each stack
+/// that isn't, the nearest crate it wants is dug out and moved across, with
+/// the crates that were above it put on the stack being fixed (it's about to
+/// be covered anyway) or parked. Digging under a settled stack's top holds
+/// that crate on a third stack meanwhile, so it stays settled. A stack meant
+/// to be empty is cleared the same way.
+fn dig(crane: &dyn Crane, start: &Stacks, target: &[Option<String>]) -> Result<Option<Vec<Move>>> {
+    let want: BTreeMap<usize, Option<&String>> = start
+        .keys()
+        .zip(target)
+        .map(|(&l, t)| (l, t.as_ref()))
+        .collect();
+    let mut stacks = start.clone();
+    let mut moves = vec![];
+    // each round settles a stack without unsettling any other
+    for _ in 0..=want.len() {
+        let Some(&s) = want.keys().find(|&&l| !settled(&stacks, &want, l)) else {
+            return Ok(Some(moves));
+        };
+        let next = match want[&s] {
+            None => park(&stacks, &want, stacks[&s].len(), s),
+            Some(c) => {
+                // (on a settled stack, crates above it, stack it's on)
+                let found = want
+                    .keys()
+                    .flat_map(|&q| {
+                        let v = &stacks[&q];
+                        let under_settled = q != s && settled(&stacks, &want, q);
+                        v.iter()
+                            .enumerate()
+                            .filter(move |&(i, x)| x == c && !(under_settled && i + 1 == v.len()))
+                            .map(move |(i, _)| (under_settled, v.len() - 1 - i, q))
+                    })
+                    .min();
+                match found {
+                    None => None,
+                    Some((_, 0, q)) => Some(vec![Move {
+                        qty: 1,
+                        from: q,
+                        to: s,
+                    }]),
+                    Some((_, above, q)) if q == s => park(&stacks, &want, above, s),
+                    Some((false, above, q)) => Some(vec![
+                        Move {
+                            qty: above,
+                            from: q,
+                            to: s,
+                        },
+                        Move {
+                            qty: 1,
+                            from: q,
+                            to: s,
+                        },
+                    ]),
+                    // keep the top of `q` on a third stack while digging under it
+                    Some((true, above, q)) => {
+                        let hold = want.keys().copied().find(|&l| l != s && l != q);
+                        hold.map(|hold| {
+                            let mut v = vec![Move {
+                                qty: 1,
+                                from: q,
+                                to: hold,
+                            }];
+                            if above > 1 {
+                                v.push(Move {
+                                    qty: above - 1,
+                                    from: q,
+                                    to: s,
+                                });
+                            }
+                            v.push(Move {
+                                qty: 1,
+                                from: q,
+                                to: s,
+                            });
+                            v.push(Move {
+                                qty: 1,
+                                from: hold,
+                                to: q,
+                            });
+                            v
+                        })
+                    }
+                }
+            }
+        };
+        let Some(next) = next else {
+            return Ok(None);
+        };
+        for mv in next {
+            crane.apply(&mut stacks, &mv)?;
+            moves.push(mv);
+        }
+    }
+    Ok(None)
+}
+
+fn settled(stacks: &Stacks, want: &BTreeMap<usize, Option<&String>>, l: usize) -> bool {
+    stacks[&l].back() == want[&l]
+}
+
+/// Moves that put the top `qty` crates of `from` out of the way
+///
+/// That's on a stack that isn't settled, ideally one that wants a crate, or
+/// if they all are then under the top of one that wants a crate, by holding
+/// that crate on a third stack meanwhile.
+fn park(
+    stacks: &Stacks,
+    want: &BTreeMap<usize, Option<&String>>,
+    qty: usize,
+    from: usize,
+) -> Option<Vec<Move>> {
+    let others = || want.keys().copied().filter(move |&l| l != from);
+    let free = others()
+        .filter(|&l| !settled(stacks, want, l))
+        .min_by_key(|l| want[l].is_none());
+    if let Some(to) = free {
+        return Some(vec![Move { qty, from, to }]);
+    }
+    let under = others().find(|l| want[l].is_some())?;
+    let hold = others().find(|&l| l != under)?;
+    Some(vec![
+        Move {
+            qty: 1,
+            from: under,
+            to: hold,
+        },
+        Move {
+            qty,
+            from,
+            to: under,
+        },
+        Move {
+            qty: 1,
+            from: hold,
+            to: under,
+        },
+    ])
+}
@@ -0,0 +1,127 @@
+//! Splitting a stream into frames that each begin after a marker
+use crate::stream::{feed, Detector};
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+
+/// A run of payload bytes following a marker, as offsets into the stream
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Frame {
+    /// Which of the framer's levels this belongs to
+    pub level: usize,
+    /// Offset of the first byte of the marker
+    pub marker: usize,
+    /// Offset of the first payload byte, just after the marker
+    pub start: usize,
+    /// Offset just past the last payload byte: where the next marker begins,
+    /// or the end of the stream
+    pub end: usize,
+}
+
+impl Frame {
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+}
+
+struct Level {
+    detector: Detector,
+    /// The frame we're part-way through, as (marker, start)
+    open: Option<(usize, usize)>,
+}
+
+/// Per-level detectors, and the frames they've completed
+struct Levels {
+    levels: Vec<Level>,
+    pos: usize,
+    ready: VecDeque<Frame>,
+}
+
+impl Levels {
+    fn push(&mut self, byte: u8) {
+        self.pos += 1;
+        for (i, level) in self.levels.iter_mut().enumerate() {
+            if level.detector.push(byte) {
+                let marker = self.pos - level.detector.window();
+                if let Some((m, start)) = level.open {
+                    self.ready.push_back(Frame {
+                        level: i,
+                        marker: m,
+                        start,
+                        end: marker,
+                    });
+                }
+                level.open = Some((marker, self.pos));
+                level.detector.reset();
+            }
+        }
+    }
+
+    fn close_all(&mut self) {
+        for (i, level) in self.levels.iter_mut().enumerate() {
+            if let Some((marker, start)) = level.open.take() {
+                self.ready.push_back(Frame {
+                    level: i,
+                    marker,
+                    start,
+                    end: self.pos,
+                });
+            }
+        }
+    }
+}
+
+/// Frames every level at once in a single pass over the stream
+///
+/// Each level has its own marker window (4 for packets and 14 for messages,
+/// say). Once a marker is found its level starts looking afresh, so markers
+/// within a level never overlap and frames are never negative.
+/// Frames come out in the order they end.
+pub struct Framer<R> {
+    reader: R,
+    state: Levels,
+    finished: bool,
+}
+
+impl<R: BufRead> Framer<R> {
+    pub fn new(reader: R, windows: &[usize]) -> Self {
+        Framer {
+            reader,
+            state: Levels {
+                levels: windows
+                    .iter()
+                    .map(|&w| Level {
+                        detector: Detector::new(w),
+                        open: None,
+                    })
+                    .collect(),
+                pos: 0,
+                ready: VecDeque::new(),
+            },
+            finished: false,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Framer<R> {
+    type Item = io::Result<Frame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.state.ready.is_empty() && !self.finished {
+            // read until some level completes a frame
+            let state = &mut self.state;
+            let result = feed(&mut self.reader, |b| {
+                state.push(b);
+                !state.ready.is_empty()
+            });
+            match result {
+                Ok((_, false)) => {
+                    self.finished = true;
+                    self.state.close_all();
+                }
+                Ok((_, true)) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        self.state.ready.pop_front().map(Ok)
+    }
+}
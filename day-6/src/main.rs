@@ -1,77 +1,222 @@
 /// 2022 Advent of Code Day 6
-use std::collections::{BTreeMap, BTreeSet};
-use std::io::{stdin, Read};
+mod framer;
+mod stream;
 
-fn main() -> std::io::Result<()> {
-    let input = {
-        let mut i = String::new();
-        stdin().read_to_string(&mut i)?;
-        i.chars().collect::<Vec<char>>()
-    };
+use framer::Framer;
+use std::io::{self, stdin};
+use stream::first_marker;
 
-    println!("Part A: {}", find_marker(&input, 4));
-    println!("Part B: {}", find_marker(&input, 14));
+fn main() -> io::Result<()> {
+    let show = |x: Option<usize>| x.map_or(String::from("no marker"), |n| n.to_string());
+
+    // `<window>` finds just the first marker of that size
+    if let Some(arg) = std::env::args().nth(1) {
+        let window = match arg.parse() {
+            Ok(w) if w > 0 => w,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{arg:?} is not a window size"),
+                ))
+            }
+        };
+        println!("{}", show(first_marker(stdin().lock(), window)?));
+        return Ok(());
+    }
+
+    // Frame packets and messages in one pass, so stdin can be as big as it likes
+    let mut first = [None, None];
+    let mut frames = [0, 0];
+    let mut payload = [0, 0];
+    for frame in Framer::new(stdin().lock(), &[4, 14]) {
+        let frame = frame?;
+        first[frame.level].get_or_insert(frame.start);
+        frames[frame.level] += 1;
+        payload[frame.level] += frame.len();
+    }
+
+    println!("Part A: {}", show(first[0]));
+    println!("Part B: {}", show(first[1]));
+    eprintln!(
+        "{} packets ({} bytes), {} messages ({} bytes)",
+        frames[0], payload[0], frames[1], payload[1]
+    );
 
     Ok(())
 }
 
-#[allow(dead_code)]
-/// Both markers are a sequence of `n` distinct characters
-/// For the start-of-packet marker it's where the four most
-/// recently received characters were *all* different
-/// So we need a sliding last-4-chars window
-/// Example: start of packet is at position 7
-///     mjqjpqmgbljsphdztnvjfqwrcgsmlb
-/// Similarly, the start-of-message marker is window size 14
-fn find_marker_simple(input: &[char], window_size: usize) -> usize {
-    for (i, c) in input.windows(window_size).enumerate() {
-        // test that all `window_size` elements are different
-        let t = BTreeSet::from_iter(c.iter());
-        if t.len() == window_size {
-            return i + window_size;
+#[cfg(test)]
+mod test {
+    use super::framer::*;
+    use super::stream::*;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    // how markers were found before streaming, as a reference
+
+    /// Both markers are a sequence of `n` distinct characters
+    /// For the start-of-packet marker it's where the four most
+    /// recently received characters were *all* different
+    /// So we need a sliding last-4-chars window
+    /// Example: start of packet is at position 7
+    ///     mjqjpqmgbljsphdztnvjfqwrcgsmlb
+    /// Similarly, the start-of-message marker is window size 14
+    fn find_marker_simple(input: &[char], window_size: usize) -> usize {
+        for (i, c) in input.windows(window_size).enumerate() {
+            // test that all `window_size` elements are different
+            let t = BTreeSet::from_iter(c.iter());
+            if t.len() == window_size {
+                return i + window_size;
+            }
         }
+        0
     }
-    0
-}
 
-/// Both markers are a sequence of `n` distinct characters
-/// For the start-of-packet marker it's where the four most
-/// recently received characters were *all* different
-/// So we need a sliding last-4-chars window
-/// Example: start of packet is at position 7
-///     mjqjpqmgbljsphdztnvjfqwrcgsmlb
-/// Similarly, the start-of-message marker is window size 14
-fn find_marker(input: &[char], window_size: usize) -> usize {
-    // don't recreate a btree every iteration
-    let mut counts: BTreeMap<char, usize> = BTreeMap::new();
-
-    // Fill map with initial window (minus one)
-    // the final char is added as part of the main loop
-    for c in &input[..window_size - 1] {
-        *counts.entry(*c).or_insert(0) += 1;
-    }
+    /// Both markers are a sequence of `n` distinct characters
+    /// For the start-of-packet marker it's where the four most
+    /// recently received characters were *all* different
+    /// So we need a sliding last-4-chars window
+    /// Example: start of packet is at position 7
+    ///     mjqjpqmgbljsphdztnvjfqwrcgsmlb
+    /// Similarly, the start-of-message marker is window size 14
+    fn find_marker(input: &[char], window_size: usize) -> usize {
+        // don't recreate a btree every iteration
+        let mut counts: BTreeMap<char, usize> = BTreeMap::new();
 
-    for (i, c) in input.windows(window_size).enumerate() {
-        let oldest = c[0];
-        let newest = *c.last().unwrap();
+        // Fill map with initial window (minus one)
+        // the final char is added as part of the main loop
+        for c in &input[..window_size - 1] {
+            *counts.entry(*c).or_insert(0) += 1;
+        }
 
-        // update newest
-        *counts.entry(newest).or_insert(0) += 1;
+        for (i, c) in input.windows(window_size).enumerate() {
+            let oldest = c[0];
+            let newest = *c.last().unwrap();
 
-        // test for condition
-        if (counts.len() == window_size) && counts.values().all(|x| x == &1) {
-            return i + window_size;
+            // update newest
+            *counts.entry(newest).or_insert(0) += 1;
+
+            // test for condition
+            if (counts.len() == window_size) && counts.values().all(|x| x == &1) {
+                return i + window_size;
+            }
+
+            // decrement oldest and remove if needed
+            if let Some(n) = counts.get(&oldest) {
+                if n <= &1 {
+                    counts.remove(&oldest);
+                } else {
+                    counts.insert(oldest, n - 1);
+                }
+            }
         }
 
-        // decrement oldest and remove if needed
-        if let Some(n) = counts.get(&oldest) {
-            if n <= &1 {
-                counts.remove(&oldest);
-            } else {
-                counts.insert(oldest, n - 1);
+        0
+    }
+
+    const SAMPLES: [(&str, usize, usize); 3] = [
+        ("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 7, 19),
+        ("bvwbjplbgvbhsrlpgdmjqwftvncz", 5, 23),
+        ("nppdvjthqldpwncqszvftbrmjlhg", 6, 23),
+    ];
+
+    #[test]
+    fn streaming_matches_slices() -> std::io::Result<()> {
+        for (s, a, b) in SAMPLES {
+            let chars: Vec<char> = s.chars().collect();
+            for (window, expected) in [(4, a), (14, b)] {
+                assert_eq!(first_marker(s.as_bytes(), window)?, Some(expected));
+                assert_eq!(find_marker(&chars, window), expected);
+                assert_eq!(find_marker_simple(&chars, window), expected);
             }
         }
+        assert_eq!(first_marker("aaaaaaaa".as_bytes(), 4)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn every_marker() -> std::io::Result<()> {
+        let found: Vec<usize> =
+            Markers::new("abcdeaaabcd".as_bytes(), 4).collect::<Result<_, _>>()?;
+        assert_eq!(found, vec![4, 5, 6, 11]);
+        Ok(())
+    }
+
+    #[test]
+    fn fast_matches_slow() {
+        for (s, a, b) in SAMPLES {
+            assert_eq!(find_marker_fast(s.as_bytes(), 4), Some(a));
+            assert_eq!(find_marker_fast(s.as_bytes(), 14), Some(b));
+        }
+        let stream = random_stream(100_000, 15, 1);
+        let chars: Vec<char> = stream.iter().map(|&b| b as char).collect();
+        for window in [1, 2, 4, 14, 15, 16] {
+            let slow = find_marker_simple(&chars, window);
+            assert_eq!(find_marker_fast(&stream, window).unwrap_or(0), slow);
+        }
+    }
+
+    #[test]
+    fn frames_between_markers() -> std::io::Result<()> {
+        // packet markers at 0..4 and 6..10, message marker at 6..12
+        let input = "abcdxxxefghyy";
+        let frames: Vec<Frame> =
+            Framer::new(input.as_bytes(), &[4, 6]).collect::<Result<_, _>>()?;
+        let packets: Vec<(usize, usize, usize)> = frames
+            .iter()
+            .filter(|f| f.level == 0)
+            .map(|f| (f.marker, f.start, f.end))
+            .collect();
+        assert_eq!(packets, vec![(0, 4, 6), (6, 10, 13)]);
+        let messages: Vec<&Frame> = frames.iter().filter(|f| f.level == 1).collect();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(
+            (messages[0].marker, messages[0].start, messages[0].len()),
+            (6, 12, 1)
+        );
+        Ok(())
     }
 
-    0
+    /// Letters from an alphabet of the given size, via xorshift
+    fn random_stream(len: usize, alphabet: u8, seed: u64) -> Vec<u8> {
+        let mut x = seed.max(1);
+        (0..len)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                b'a' + (x % alphabet as u64) as u8
+            })
+            .collect()
+    }
+
+    /// Timings on streams that have no marker at all, so every
+    /// detector has to get to the end. Run with
+    /// `cargo test --release -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_detectors() {
+        use std::time::Instant;
+        let len = 10_000_000;
+        for window in [4, 14] {
+            let stream = random_stream(len, window as u8 - 1, 42);
+            let chars: Vec<char> = stream.iter().map(|&b| b as char).collect();
+
+            let t = Instant::now();
+            let simple = find_marker_simple(&chars, window);
+            let t_simple = t.elapsed();
+
+            let t = Instant::now();
+            let btree = find_marker(&chars, window);
+            let t_btree = t.elapsed();
+
+            let t = Instant::now();
+            let fast = find_marker_fast(&stream, window);
+            let t_fast = t.elapsed();
+
+            assert_eq!((simple, btree, fast), (0, 0, None));
+            println!(
+                "{len} bytes, window {window}: simple {t_simple:?}, btree {t_btree:?}, counters {t_fast:?}"
+            );
+        }
+    }
 }
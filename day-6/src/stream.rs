@@ -0,0 +1,142 @@
+//! Marker detection over a byte stream, holding only the current window
+use std::io::{self, BufRead};
+
+/// Sliding window over the most recent `window` bytes
+///
+/// Rather than asking whether everything in the window is distinct,
+/// keep a running tally of how many bytes are repeated within it:
+/// each step touches one counter on the way in and one on the way out.
+pub struct Detector {
+    window: usize,
+    /// Ring buffer of the last `window` bytes
+    recent: Vec<u8>,
+    /// Where the next byte goes (and the oldest currently is, once full)
+    head: usize,
+    filled: usize,
+    counts: [u32; 256],
+    /// Number of surplus copies in the window: zero means all distinct
+    dups: usize,
+}
+
+impl Detector {
+    pub fn new(window: usize) -> Self {
+        assert!(window > 0, "a marker needs at least one byte");
+        Detector {
+            window,
+            recent: vec![0; window],
+            head: 0,
+            filled: 0,
+            counts: [0; 256],
+            dups: 0,
+        }
+    }
+
+    /// Forget the window so far, e.g. to look for a fresh marker
+    pub fn reset(&mut self) {
+        self.head = 0;
+        self.filled = 0;
+        self.counts = [0; 256];
+        self.dups = 0;
+    }
+
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// Take in the next byte; true if the window now ending here is a marker
+    pub fn push(&mut self, byte: u8) -> bool {
+        if self.filled == self.window {
+            let oldest = self.recent[self.head] as usize;
+            self.counts[oldest] -= 1;
+            if self.counts[oldest] > 0 {
+                self.dups -= 1;
+            }
+        } else {
+            self.filled += 1;
+        }
+
+        let newest = byte as usize;
+        if self.counts[newest] > 0 {
+            self.dups += 1;
+        }
+        self.counts[newest] += 1;
+        self.recent[self.head] = byte;
+        self.head = (self.head + 1) % self.window;
+
+        self.filled == self.window && self.dups == 0
+    }
+}
+
+/// Every marker in a stream, as the number of bytes read when it completes
+///
+/// Markers may overlap: in `abcde` with a window of 4, both 4 and 5 are reported.
+pub struct Markers<R> {
+    reader: R,
+    detector: Detector,
+    pos: usize,
+}
+
+impl<R: BufRead> Markers<R> {
+    pub fn new(reader: R, window: usize) -> Self {
+        Markers {
+            reader,
+            detector: Detector::new(window),
+            pos: 0,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Markers<R> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let detector = &mut self.detector;
+        match feed(&mut self.reader, |b| detector.push(b)) {
+            Ok((used, found)) => {
+                self.pos += used;
+                found.then_some(Ok(self.pos))
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// Hand bytes to `take` until it returns true or the stream runs out,
+/// consuming only what it was given
+///
+/// Returns how many bytes that was, and whether `take` asked to stop.
+pub fn feed<R: BufRead>(
+    reader: &mut R,
+    mut take: impl FnMut(u8) -> bool,
+) -> io::Result<(usize, bool)> {
+    let mut total = 0;
+    loop {
+        let buf = match reader.fill_buf() {
+            Ok([]) => return Ok((total, false)),
+            Ok(buf) => buf,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let (used, stop) = match buf.iter().position(|&b| take(b)) {
+            Some(i) => (i + 1, true),
+            None => (buf.len(), false),
+        };
+        reader.consume(used);
+        total += used;
+        if stop {
+            return Ok((total, true));
+        }
+    }
+}
+
+/// The first marker in the stream, if there is one
+pub fn first_marker<R: BufRead>(reader: R, window: usize) -> io::Result<Option<usize>> {
+    Markers::new(reader, window).next().transpose()
+}
+
+/// The first marker in a byte slice, if there is one
+#[cfg(test)]
+pub fn find_marker_fast(input: &[u8], window: usize) -> Option<usize> {
+    let mut detector = Detector::new(window);
+    input.iter().position(|&b| detector.push(b)).map(|i| i + 1)
+}
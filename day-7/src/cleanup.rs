@@ -0,0 +1,202 @@
+//! Choosing what to delete to make room
+use crate::tree::{FsTree, NodeId};
+
+/// How big the disk is and how much of it must be free
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DiskPolicy {
+    pub capacity: usize,
+    pub required_free: usize,
+}
+
+impl Default for DiskPolicy {
+    /// The numbers for the device in the puzzle
+    fn default() -> Self {
+        DiskPolicy {
+            capacity: 70_000_000,
+            required_free: 30_000_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub path: String,
+    pub size: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Plan {
+    /// There's already enough space
+    NothingNeeded { free: usize },
+    Delete {
+        /// How much has to go
+        to_free: usize,
+        /// The smallest directory that frees at least `to_free` by itself, as
+        /// the puzzle asks; that can be `/` itself
+        single: Candidate,
+        /// As few directories below `/` as possible, none inside another;
+        /// empty if even all of them together aren't enough, which can
+        /// happen because deleting them leaves the files directly in `/`
+        fewest: Vec<Candidate>,
+    },
+    /// Not even deleting `/` would do it
+    Impossible { to_free: usize },
+}
+
+impl DiskPolicy {
+    pub fn plan(&self, tree: &FsTree) -> Plan {
+        let sizes = tree.sizes();
+        let used = sizes[FsTree::ROOT];
+        let to_free = (used + self.required_free).saturating_sub(self.capacity);
+        if to_free == 0 {
+            return Plan::NothingNeeded {
+                free: self.capacity.saturating_sub(used),
+            };
+        }
+
+        let candidate = |d: NodeId| Candidate {
+            path: tree.path(d),
+            size: sizes[d],
+        };
+
+        // enough means at least `to_free`, for one directory or several
+        let enough = |size: usize| size >= to_free;
+
+        let Some(single) = tree
+            .dirs()
+            .filter(|d| enough(sizes[*d]))
+            .min_by_key(|d| sizes[*d])
+        else {
+            return Plan::Impossible { to_free };
+        };
+
+        // Any directory is no bigger than the top-level one it's in,
+        // so the fewest directories that could do it are the biggest top-level ones
+        let mut top: Vec<NodeId> = tree
+            .children(FsTree::ROOT)
+            .into_iter()
+            .filter(|d| tree.is_dir(*d))
+            .collect();
+        top.sort_by_key(|d| std::cmp::Reverse(sizes[*d]));
+        let mut chosen = vec![];
+        let mut total = 0;
+        for d in top {
+            if enough(total) {
+                break;
+            }
+            total += sizes[d];
+            chosen.push(d);
+        }
+        if !enough(total) {
+            chosen.clear();
+        }
+
+        // Now free as little as we can without needing more directories:
+        // swap each choice for the smallest alternative that still does the job
+        let nested = |a: NodeId, b: NodeId| is_within(tree, a, b) || is_within(tree, b, a);
+        let mut improved = true;
+        while improved {
+            improved = false;
+            for i in 0..chosen.len() {
+                let rest = total - sizes[chosen[i]];
+                let better = tree
+                    .dirs()
+                    .filter(|d| *d != FsTree::ROOT && sizes[*d] < sizes[chosen[i]])
+                    .filter(|d| enough(rest + sizes[*d]))
+                    .filter(|d| {
+                        chosen
+                            .iter()
+                            .enumerate()
+                            .all(|(j, c)| j == i || !nested(*c, *d))
+                    })
+                    .min_by_key(|d| sizes[*d]);
+                if let Some(d) = better {
+                    total = rest + sizes[d];
+                    chosen[i] = d;
+                    improved = true;
+                }
+            }
+        }
+
+        Plan::Delete {
+            to_free,
+            single: candidate(single),
+            fewest: chosen.into_iter().map(candidate).collect(),
+        }
+    }
+}
+
+/// Is `inner` the same as, or somewhere below, `outer`?
+fn is_within(tree: &FsTree, inner: NodeId, outer: NodeId) -> bool {
+    let mut cur = Some(inner);
+    while let Some(c) = cur {
+        if c == outer {
+            return true;
+        }
+        cur = tree.parent(c);
+    }
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn plans() -> Result<()> {
+        let mut t = FsTree::new();
+        let a = t.add_dir(FsTree::ROOT, "a")?;
+        let b = t.add_dir(FsTree::ROOT, "b")?;
+        let c = t.add_dir(a, "c")?;
+        t.add_file(a, "x", 30)?;
+        t.add_file(c, "y", 20)?;
+        t.add_file(b, "z", 40)?;
+        t.add_file(FsTree::ROOT, "w", 10)?;
+        // a: 50, b: 40, c: 20, total 100
+
+        let policy = |required_free| DiskPolicy {
+            capacity: 150,
+            required_free,
+        };
+        assert_eq!(policy(50).plan(&t), Plan::NothingNeeded { free: 50 });
+
+        let Plan::Delete { single, fewest, .. } = policy(85).plan(&t) else {
+            panic!("expected a plan")
+        };
+        assert_eq!(single.path, "/b/");
+        assert_eq!(fewest.len(), 1);
+
+        let Plan::Delete { single, fewest, .. } = policy(110).plan(&t) else {
+            panic!("expected a plan")
+        };
+        // only the whole disk is big enough by itself
+        assert_eq!(single.path, "/");
+        let mut paths: Vec<String> = fewest.into_iter().map(|c| c.path).collect();
+        paths.sort();
+        assert_eq!(paths, vec!["/a/c/", "/b/"]);
+
+        // b is exactly enough, by itself or as the only one of the fewest
+        let Plan::Delete { single, fewest, .. } = policy(90).plan(&t) else {
+            panic!("expected a plan")
+        };
+        assert_eq!(single.path, "/b/");
+        assert_eq!(fewest, vec![single]);
+
+        // a and b together are 90, as w stays in /
+        let Plan::Delete { single, fewest, .. } = policy(145).plan(&t) else {
+            panic!("expected a plan")
+        };
+        assert_eq!(single.path, "/");
+        assert!(fewest.is_empty());
+
+        // / is exactly the size needed, which is enough
+        let Plan::Delete { single, fewest, .. } = policy(150).plan(&t) else {
+            panic!("expected a plan")
+        };
+        assert_eq!(single.path, "/");
+        assert!(fewest.is_empty());
+        assert_eq!(policy(151).plan(&t), Plan::Impossible { to_free: 101 });
+        Ok(())
+    }
+}
@@ -1,195 +1,95 @@
-use std::collections::BTreeMap;
-/// AOC Day 7
+//! AOC Day 7
+//!
+//! We have a terminal session to read through
+//! From this we need to reconstruct a file tree
 
-/// We have a terminal session to read through
-/// From this we need to reconstruct a file tree
+mod cleanup;
+mod transcript;
+mod tree;
+
+use anyhow::{bail, Context, Result};
+use cleanup::{DiskPolicy, Plan};
+use std::io::stdin;
+use transcript::Session;
+use tree::FsTree;
 
 // $ cd / : return cursor to top of tree
 // $ cd .. : go up one level in tree
+// $ cd a/../b, $ cd /a/b : any mix of the above
+
+/// Given a directory as an argument, instead print
+/// a session that would explore it; given `tree` and optionally a depth,
+/// print the tree the session describes the way `tree -h` would
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let show_tree = match args.as_slice() {
+        [] => None,
+        [cmd] if cmd == "tree" => Some(None),
+        [cmd, depth] if cmd == "tree" => Some(Some(
+            depth
+                .parse()
+                .with_context(|| format!("{depth:?} is not a depth"))?,
+        )),
+        [dir] => {
+            print!("{}", transcript::generate(&FsTree::from_dir(dir)?));
+            return Ok(());
+        }
+        _ => bail!("Usage: day-7 [<dir> | tree [<depth>]]"),
+    };
 
-fn main() -> std::io::Result<()> {
     // construct a tree
-
-    let mut filetree: BTreeMap<Vec<String>, BTreeMap<FileName, usize>> = BTreeMap::new();
-
-    let mut curdir_path = vec![];
-
-    // Parse loop
-    for lrez in stdin().lines() {
-        let llll = lrez.unwrap();
-        let line = llll.trim();
-
-        let what = parse_line(line);
-
-        // eprintln!("{}\n{:?}", line, what);
-
-        match what {
-            LineType::cmd(c) => {
-                match c {
-                    Command::ls => {
-                        // Files listed by ls may need to be created in tree
-                    }
-                    Command::cd(l) => match l {
-                        Location::Root => {
-                            curdir_path.clear();
-                        }
-                        Location::Up => {
-                            let _ = curdir_path.pop();
-                        }
-                        Location::Down(f) => curdir_path.push(f.clone()),
-                    },
-                }
-            }
-            LineType::output(node) => {
-                // OK, we have to insert
-                match node {
-                    Node::File(name, size) => {
-                        filetree
-                            .entry(curdir_path.clone())
-                            .or_default()
-                            .insert(name, size);
-                    }
-                    _ => {}
-                }
-            }
-        }
+    let lines = stdin().lines().collect::<std::io::Result<Vec<String>>>()?;
+    let session = Session::parse(lines);
+    for w in &session.warnings {
+        eprintln!("Warning: {w}");
     }
+    let filetree = session.tree;
 
     // Now that we have constructed a file tree we can do operations on it
 
-    //     eprintln!("Directs:");
-
-    let directs: BTreeMap<Vec<String>, usize> = BTreeMap::from_iter(
-        filetree
-            .iter()
-            .map(|(path, files)| (path.clone(), files.values().sum::<usize>())), //             .inspect(|(p, v)| eprintln!("{:?} {}", p, v))
-    );
-
-    // ^^ this is almost correct.
-    // it gets the size of each directory from the DIRECT contents
-    // but does not account for INDIRECT contents
-
-    // We'll build up an indirect listing as follows
-
-    let maxdepth = filetree.keys().map(|k| k.len()).max().unwrap_or(0);
-
-    let mut running_totals = directs.clone();
-
-    for iii in 0..maxdepth {
-        let i = maxdepth - iii;
-        //         eprintln!("{i}");
-        let to_add: Vec<(Vec<String>, usize)> = running_totals
-            .iter()
-            .filter(|(k, _)| k.len() == i)
-            .map(|(k, v)| (k.clone(), v.clone()))
-            .collect();
-        for (k, v) in to_add {
-            let mut parent = k.clone();
-            parent.pop();
-            *(running_totals.entry(parent).or_default()) += v;
-        }
+    if let Some(depth) = show_tree {
+        print!("{}", filetree.tree(depth));
+        return Ok(());
     }
 
-    //     eprintln!("With indirects:");
-    //
-    //     for (k, v) in &running_totals {
-    //         eprintln!("{:?} {}", k, v);
-    //     }
+    eprint!("{}", filetree.du(Some(1)));
 
-    eprintln!("");
+    let sizes = filetree.sizes();
+    let dir_sizes: Vec<usize> = filetree.dirs().map(|d| sizes[d]).collect();
 
     println!(
         "Part A: {}",
-        running_totals
+        dir_sizes
             .iter()
-            .filter(|(_, v)| v < &&100_000_usize)
-            //             .inspect(|(k, v)| eprintln!("{:?} {}", k, v))
-            .map(|(_, v)| *v)
+            .filter(|v| v < &&100_000_usize)
             .sum::<usize>()
     );
 
-    // eprintln!("{:#?}", filetree);
-
     // PART B
 
-    let max_disk = 70_000_000_usize;
-    let space_needed = 30_000_000;
-    let total_used = running_totals.get(&vec![]).unwrap();
-    let min_free = space_needed - (max_disk - total_used);
-
-    // We need to find the smallest directory that is larger than min_free
+    // We need to find the smallest directory that frees at least what must be freed
 
-    println!(
-        "Part B: {}",
-        running_totals
-            .iter()
-            .filter(|(_, v)| v > &&min_free)
-            //             .inspect(|(k, v)| eprintln!("{:?} {}", k, v))
-            .map(|(_, v)| *v)
-            .min()
-            .unwrap()
-    );
-
-    Ok(())
-}
-
-#[derive(Debug)]
-enum Node {
-    Dir,
-    File(FileName, usize),
-}
-
-type FileName = String;
-
-#[derive(Debug)]
-enum Location {
-    Root,
-    Up,
-    Down(FileName),
-}
-#[derive(Debug)]
-enum Command {
-    cd(Location),
-    ls,
-}
-
-#[derive(Debug)]
-enum LineType {
-    cmd(Command),
-    output(Node),
-}
-
-fn parse_line(input: &str) -> LineType {
-    if input.starts_with('$') {
-        return LineType::cmd(parse_command(input.strip_prefix("$ ").unwrap()));
-    } else {
-        return LineType::output(parse_file(input));
-    }
-}
-
-fn parse_file(input: &str) -> Node {
-    if input.starts_with("dir") {
-        return Node::Dir;
-    } else {
-        if let Some((sz, nm)) = input.split_once(' ') {
-            if let Ok(size) = sz.parse() {
-                return Node::File(String::from(nm), size);
+    match DiskPolicy::default().plan(&filetree) {
+        Plan::NothingNeeded { free } => {
+            println!("Part B: nothing needs deleting, {free} already free")
+        }
+        Plan::Impossible { to_free } => {
+            bail!("Can't free {to_free} even by deleting /")
+        }
+        Plan::Delete {
+            to_free,
+            single,
+            fewest,
+        } => {
+            println!("Part B: {} ({})", single.size, single.path);
+            if fewest.is_empty() {
+                eprintln!("No directories below / free {to_free} between them");
+            } else {
+                let paths: Vec<&str> = fewest.iter().map(|c| c.path.as_str()).collect();
+                eprintln!("Fewest directories to free {to_free}: {}", paths.join(" "));
             }
         }
-        panic!("This isn't supposed to happen")
     }
-}
 
-fn parse_command(input: &str) -> Command {
-    if input.starts_with("cd") {
-        let location = input.strip_prefix("cd ").unwrap();
-        return match location {
-            "/" => Command::cd(Location::Root),
-            ".." => Command::cd(Location::Up),
-            _ => Command::cd(Location::Down(String::from(location))),
-        };
-    } else if input.starts_with("ls") {
-        return Command::ls;
-    }
-    panic!("This isn't supposed to happen!")
+    Ok(())
 }
@@ -0,0 +1,356 @@
+//! Reading the terminal session back into a tree
+use crate::tree::{Entry, FsTree, NodeId};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{self, Write};
+
+type FileName = String;
+
+/// A line of `ls` output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    Dir(FileName),
+    File(FileName, usize),
+}
+
+impl Node {
+    fn name(&self) -> &str {
+        match self {
+            Node::Dir(n) | Node::File(n, _) => n,
+        }
+    }
+}
+
+/// One step of a `cd` path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    Root,
+    Up,
+    Down(FileName),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// `cd /a/b`, `cd ../c` and so on, split into steps
+    Cd(Vec<Location>),
+    Ls,
+    Unknown(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineType {
+    Cmd(Command),
+    Output(Node),
+}
+
+/// Something odd about the session that didn't stop us reading it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    UnknownCommand {
+        line: usize,
+        command: String,
+    },
+    /// Output that isn't in response to an `ls`
+    StrayOutput {
+        line: usize,
+        text: String,
+    },
+    Unparseable {
+        line: usize,
+        text: String,
+    },
+    /// Disagreement with something seen earlier
+    Conflict {
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::UnknownCommand { line, command } => {
+                write!(
+                    f,
+                    "line {line}: unknown command {command:?}, output ignored"
+                )
+            }
+            Warning::StrayOutput { line, text } => {
+                write!(f, "line {line}: output {text:?} without an ls")
+            }
+            Warning::Unparseable { line, text } => {
+                write!(f, "line {line}: can't make sense of {text:?}")
+            }
+            Warning::Conflict { line, message } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+
+pub fn parse_line(input: &str) -> Option<LineType> {
+    if let Some(cmd) = input.strip_prefix('$') {
+        Some(LineType::Cmd(parse_command(cmd.trim())))
+    } else {
+        parse_file(input).map(LineType::Output)
+    }
+}
+
+fn parse_file(input: &str) -> Option<Node> {
+    if let Some(name) = input.strip_prefix("dir ") {
+        return Some(Node::Dir(String::from(name)));
+    }
+    let (sz, nm) = input.split_once(' ')?;
+    Some(Node::File(String::from(nm), sz.parse().ok()?))
+}
+
+pub fn parse_command(input: &str) -> Command {
+    match input.split_once(' ').unwrap_or((input, "")) {
+        ("cd", path) => Command::Cd(parse_path(path.trim())),
+        ("ls", "") => Command::Ls,
+        _ => Command::Unknown(String::from(input)),
+    }
+}
+
+/// `/a/b/../c` becomes Root, a, b, Up, c; `.` and empty steps are dropped
+fn parse_path(path: &str) -> Vec<Location> {
+    let mut out = vec![];
+    if path.starts_with('/') {
+        out.push(Location::Root);
+    }
+    for step in path.split('/') {
+        match step {
+            "" | "." => (),
+            ".." => out.push(Location::Up),
+            name => out.push(Location::Down(String::from(name))),
+        }
+    }
+    out
+}
+
+/// The tree described by a session, and anything suspicious along the way
+#[derive(Debug, Default)]
+pub struct Session {
+    pub tree: FsTree,
+    pub warnings: Vec<Warning>,
+    curdir: NodeId,
+    /// The directory being listed, and what we've seen so far this time
+    listing: Option<(NodeId, BTreeSet<FileName>)>,
+    /// Everything an earlier `ls` showed, per directory
+    listed: HashMap<NodeId, BTreeSet<FileName>>,
+    /// Inside the output of an unknown command
+    ignoring: bool,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read a whole session
+    pub fn parse<I, S>(lines: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut session = Session::new();
+        let mut last = 0;
+        for (i, line) in lines.into_iter().enumerate() {
+            last = i + 1;
+            session.feed(last, line.as_ref());
+        }
+        session.finish_listing(last);
+        session
+    }
+
+    fn conflict(&mut self, line: usize, message: String) {
+        self.warnings.push(Warning::Conflict { line, message });
+    }
+
+    /// Compare a just-finished `ls` against any earlier one of the same directory
+    fn finish_listing(&mut self, line: usize) {
+        let Some((dir, names)) = self.listing.take() else {
+            return;
+        };
+        match self.listed.get(&dir) {
+            Some(before) if before != &names => {
+                let gone: Vec<&String> = before.difference(&names).collect();
+                let new: Vec<&String> = names.difference(before).collect();
+                let message = format!(
+                    "listing of {} changed: {gone:?} missing, {new:?} new",
+                    self.tree.path(dir)
+                );
+                self.conflict(line, message);
+            }
+            Some(_) => (),
+            None => {
+                self.listed.insert(dir, names);
+            }
+        }
+    }
+
+    fn cd(&mut self, line: usize, path: &[Location]) {
+        for step in path {
+            match step {
+                Location::Root => self.curdir = FsTree::ROOT,
+                Location::Up => self.curdir = self.tree.parent(self.curdir).unwrap_or(FsTree::ROOT),
+                Location::Down(name) => match self.tree.add_dir(self.curdir, name) {
+                    Ok(d) => self.curdir = d,
+                    Err(e) => {
+                        self.conflict(line, format!("cd: {e}"));
+                        return;
+                    }
+                },
+            }
+        }
+    }
+
+    /// Take in the next line of the session (numbered from 1)
+    pub fn feed(&mut self, line: usize, text: &str) {
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+        match parse_line(text) {
+            Some(LineType::Cmd(c)) => {
+                self.finish_listing(line);
+                self.ignoring = false;
+                match c {
+                    Command::Ls => self.listing = Some((self.curdir, BTreeSet::new())),
+                    Command::Cd(path) => self.cd(line, &path),
+                    Command::Unknown(command) => {
+                        self.ignoring = true;
+                        self.warnings
+                            .push(Warning::UnknownCommand { line, command });
+                    }
+                }
+            }
+            _ if self.ignoring => (),
+            Some(LineType::Output(node)) => {
+                let Some((dir, names)) = &mut self.listing else {
+                    self.warnings.push(Warning::StrayOutput {
+                        line,
+                        text: String::from(text),
+                    });
+                    return;
+                };
+                let dir = *dir;
+                names.insert(String::from(node.name()));
+                let added = match node {
+                    Node::File(name, size) => self.tree.add_file(dir, &name, size),
+                    Node::Dir(name) => self.tree.add_dir(dir, &name),
+                };
+                if let Err(e) = added {
+                    self.conflict(line, e.to_string());
+                }
+            }
+            None => self.warnings.push(Warning::Unparseable {
+                line,
+                text: String::from(text),
+            }),
+        }
+    }
+}
+
+/// The session that would have explored every directory of `tree`, in name order
+pub fn generate(tree: &FsTree) -> String {
+    fn explore(tree: &FsTree, dir: NodeId, out: &mut String) {
+        let _ = writeln!(out, "$ ls");
+        let children = tree.children(dir);
+        for c in &children {
+            let node = tree.node(*c);
+            let _ = match node.entry {
+                Entry::Dir(_) => writeln!(out, "dir {}", node.name),
+                Entry::File(size) => writeln!(out, "{size} {}", node.name),
+            };
+        }
+        for c in children.into_iter().filter(|c| tree.is_dir(*c)) {
+            let _ = writeln!(out, "$ cd {}", tree.node(c).name);
+            explore(tree, c, out);
+            let _ = writeln!(out, "$ cd ..");
+        }
+    }
+    let mut out = String::from("$ cd /\n");
+    explore(tree, FsTree::ROOT, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn paths_and_repeats() {
+        let session = Session::parse([
+            "$ cd /a/b",
+            "$ ls",
+            "10 x",
+            "dir c",
+            "$ cd c/../../b/./c",
+            "$ ls",
+            "5 y",
+            "$ cd /a/b",
+            "$ ls",
+            "10 x",
+            "dir c",
+            "$ pwd",
+            "/a/b",
+        ]);
+        let t = &session.tree;
+        let sizes = t.sizes();
+        assert_eq!(sizes[FsTree::ROOT], 15);
+        let a = t.child(FsTree::ROOT, "a").unwrap();
+        assert_eq!(sizes[a], 15);
+        assert_eq!(
+            session.warnings,
+            vec![Warning::UnknownCommand {
+                line: 12,
+                command: String::from("pwd")
+            }]
+        );
+    }
+
+    #[test]
+    fn conflicts() {
+        let session = Session::parse([
+            "$ ls", "10 x", "dir d", "$ ls", "11 x", "4 z", "$ cd x", "7 stray",
+        ]);
+        let lines: Vec<usize> = session
+            .warnings
+            .iter()
+            .map(|w| match w {
+                Warning::Conflict { line, .. } | Warning::StrayOutput { line, .. } => *line,
+                _ => 0,
+            })
+            .collect();
+        // size change, changed listing (found at the next command), cd into a file, stray
+        assert_eq!(lines, vec![5, 7, 7, 8]);
+    }
+
+    #[test]
+    fn round_trip() -> anyhow::Result<()> {
+        let input = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/input.txt"))?;
+        let session = Session::parse(input.lines());
+        assert!(session.warnings.is_empty());
+        let again = Session::parse(generate(&session.tree).lines());
+        assert!(again.warnings.is_empty());
+        assert_eq!(again.tree, session.tree);
+        Ok(())
+    }
+
+    #[test]
+    fn real_directory() -> anyhow::Result<()> {
+        let root = std::env::temp_dir().join(format!("day-7-fixture-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("a/b c"))?;
+        std::fs::create_dir_all(root.join("empty"))?;
+        std::fs::write(root.join("top.txt"), "hello")?;
+        std::fs::write(root.join("a/b c/inner"), [0u8; 1500])?;
+        let tree = FsTree::from_dir(&root);
+        std::fs::remove_dir_all(&root)?;
+        let tree = tree?;
+
+        let session = Session::parse(generate(&tree).lines());
+        assert!(session.warnings.is_empty());
+        assert_eq!(session.tree, tree);
+        assert_eq!(tree.sizes()[FsTree::ROOT], 1505);
+        assert!(tree.child(FsTree::ROOT, "empty").is_some());
+        Ok(())
+    }
+}
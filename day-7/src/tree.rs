@@ -0,0 +1,346 @@
+//! A directory tree, as pieced together from the terminal session
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// Index of a node within its tree
+pub type NodeId = usize;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Entry {
+    Dir(BTreeMap<String, NodeId>),
+    File(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub name: String,
+    pub parent: Option<NodeId>,
+    pub entry: Entry,
+}
+
+/// Directories and files, stored flat with parent and child links
+///
+/// Nodes are only ever added, and always after their parent,
+/// so a parent's id is less than any of its children's.
+#[derive(Debug, Clone)]
+pub struct FsTree {
+    nodes: Vec<Node>,
+}
+
+impl Default for FsTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FsTree {
+    pub const ROOT: NodeId = 0;
+
+    /// A tree with nothing but `/`
+    pub fn new() -> Self {
+        FsTree {
+            nodes: vec![Node {
+                name: String::from("/"),
+                parent: None,
+                entry: Entry::Dir(BTreeMap::new()),
+            }],
+        }
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id]
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id].parent
+    }
+
+    pub fn is_dir(&self, id: NodeId) -> bool {
+        matches!(self.nodes[id].entry, Entry::Dir(_))
+    }
+
+    pub fn child(&self, dir: NodeId, name: &str) -> Option<NodeId> {
+        match &self.nodes[dir].entry {
+            Entry::Dir(children) => children.get(name).copied(),
+            Entry::File(_) => None,
+        }
+    }
+
+    /// Children in name order (none, for a file)
+    pub fn children(&self, id: NodeId) -> Vec<NodeId> {
+        match &self.nodes[id].entry {
+            Entry::Dir(children) => children.values().copied().collect(),
+            Entry::File(_) => vec![],
+        }
+    }
+
+    /// Every directory, root first
+    pub fn dirs(&self) -> impl Iterator<Item = NodeId> + '_ {
+        (0..self.nodes.len()).filter(|id| self.is_dir(*id))
+    }
+
+    fn add(&mut self, parent: NodeId, name: &str, entry: Entry) -> Result<NodeId> {
+        if !self.is_dir(parent) {
+            bail!("{} is a file, not a directory", self.path(parent))
+        }
+        let id = self.nodes.len();
+        if let Entry::Dir(children) = &mut self.nodes[parent].entry {
+            children.insert(name.to_string(), id);
+        }
+        self.nodes.push(Node {
+            name: name.to_string(),
+            parent: Some(parent),
+            entry,
+        });
+        Ok(id)
+    }
+
+    /// The subdirectory `name` of `parent`, created if need be
+    pub fn add_dir(&mut self, parent: NodeId, name: &str) -> Result<NodeId> {
+        match self.child(parent, name) {
+            Some(id) if self.is_dir(id) => Ok(id),
+            Some(id) => bail!("{} is a file, not a directory", self.path(id)),
+            None => self.add(parent, name, Entry::Dir(BTreeMap::new())),
+        }
+    }
+
+    /// Record a file; seeing the same file again is fine, a different one is not
+    pub fn add_file(&mut self, parent: NodeId, name: &str, size: usize) -> Result<NodeId> {
+        match self.child(parent, name) {
+            Some(id) => match self.nodes[id].entry {
+                Entry::File(s) if s == size => Ok(id),
+                Entry::File(s) => bail!("{} was {s} bytes, now {size}", self.path(id)),
+                Entry::Dir(_) => bail!("{} is a directory, not a file", self.path(id)),
+            },
+            None => self.add(parent, name, Entry::File(size)),
+        }
+    }
+
+    /// Absolute path, with a trailing slash for directories
+    pub fn path(&self, id: NodeId) -> String {
+        let mut parts = vec![];
+        let mut cur = id;
+        while let Some(p) = self.nodes[cur].parent {
+            parts.push(self.nodes[cur].name.as_str());
+            cur = p;
+        }
+        parts.reverse();
+        let mut out = String::from("/") + &parts.join("/");
+        if id != Self::ROOT && self.is_dir(id) {
+            out.push('/');
+        }
+        out
+    }
+
+    /// Recursive size of every node, indexed by id
+    pub fn sizes(&self) -> Vec<usize> {
+        let mut sizes: Vec<usize> = self
+            .nodes
+            .iter()
+            .map(|n| match n.entry {
+                Entry::File(s) => s,
+                Entry::Dir(_) => 0,
+            })
+            .collect();
+        // children always come after their parents
+        for id in (1..self.nodes.len()).rev() {
+            if let Some(p) = self.nodes[id].parent {
+                sizes[p] += sizes[id];
+            }
+        }
+        sizes
+    }
+
+    /// The same as `sizes()[id]`, worked out the slow way to check it
+    #[cfg(test)]
+    pub fn size(&self, id: NodeId) -> usize {
+        match &self.nodes[id].entry {
+            Entry::File(s) => *s,
+            Entry::Dir(children) => children.values().map(|c| self.size(*c)).sum(),
+        }
+    }
+
+    fn depth(&self, id: NodeId) -> usize {
+        let mut depth = 0;
+        let mut cur = id;
+        while let Some(p) = self.nodes[cur].parent {
+            depth += 1;
+            cur = p;
+        }
+        depth
+    }
+
+    /// Like `du -h -d max_depth`: every directory with its total, deepest first
+    pub fn du(&self, max_depth: Option<usize>) -> String {
+        let sizes = self.sizes();
+        let mut out = String::new();
+        self.du_walk(Self::ROOT, &sizes, max_depth, &mut out);
+        out
+    }
+
+    fn du_walk(&self, id: NodeId, sizes: &[usize], max_depth: Option<usize>, out: &mut String) {
+        for c in self.children(id) {
+            if self.is_dir(c) {
+                self.du_walk(c, sizes, max_depth, out);
+            }
+        }
+        if max_depth.is_none_or(|d| self.depth(id) <= d) {
+            let _ = writeln!(out, "{}\t{}", human_size(sizes[id]), self.path(id));
+        }
+    }
+
+    /// Like `tree -h -L max_depth`
+    pub fn tree(&self, max_depth: Option<usize>) -> String {
+        let sizes = self.sizes();
+        let mut out = format!("[{:>5}]  /\n", human_size(sizes[Self::ROOT]));
+        self.tree_walk(Self::ROOT, &sizes, max_depth, "", &mut out);
+        out
+    }
+
+    fn tree_walk(
+        &self,
+        id: NodeId,
+        sizes: &[usize],
+        max_depth: Option<usize>,
+        prefix: &str,
+        out: &mut String,
+    ) {
+        if max_depth.is_some_and(|d| self.depth(id) >= d) {
+            return;
+        }
+        let children = self.children(id);
+        for (i, c) in children.iter().enumerate() {
+            let last = i + 1 == children.len();
+            let _ = writeln!(
+                out,
+                "{prefix}{}[{:>5}]  {}",
+                if last { "└── " } else { "├── " },
+                human_size(sizes[*c]),
+                self.nodes[*c].name
+            );
+            let deeper = format!("{prefix}{}", if last { "    " } else { "│   " });
+            self.tree_walk(*c, sizes, max_depth, &deeper, out);
+        }
+    }
+}
+
+/// Two trees are the same if they hold the same names and sizes in the same places,
+/// whatever order they were discovered in
+impl PartialEq for FsTree {
+    fn eq(&self, other: &Self) -> bool {
+        fn same(a: &FsTree, x: NodeId, b: &FsTree, y: NodeId) -> bool {
+            match (&a.nodes[x].entry, &b.nodes[y].entry) {
+                (Entry::File(s), Entry::File(t)) => s == t,
+                (Entry::Dir(xs), Entry::Dir(ys)) => {
+                    xs.len() == ys.len()
+                        && xs
+                            .iter()
+                            .zip(ys)
+                            .all(|((m, i), (n, j))| m == n && same(a, *i, b, *j))
+                }
+                _ => false,
+            }
+        }
+        same(self, Self::ROOT, other, Self::ROOT)
+    }
+}
+
+impl Eq for FsTree {}
+
+impl FsTree {
+    /// Read a real directory from disk; symlinks and anything
+    /// that's neither file nor directory are skipped
+    pub fn from_dir(path: impl AsRef<Path>) -> Result<Self> {
+        let mut tree = FsTree::new();
+        tree.import(path.as_ref(), Self::ROOT)?;
+        Ok(tree)
+    }
+
+    fn import(&mut self, path: &Path, dir: NodeId) -> Result<()> {
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            let meta = entry.path().symlink_metadata()?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if meta.is_dir() {
+                let id = self.add_dir(dir, &name)?;
+                self.import(&entry.path(), id)?;
+            } else if meta.is_file() {
+                self.add_file(dir, &name, meta.len() as usize)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sizes the way `du -h` shows them: 1023, 1.0K, 9.9K, 10K, 1.5M...
+pub fn human_size(bytes: usize) -> String {
+    const UNITS: [&str; 5] = ["K", "M", "G", "T", "P"];
+    if bytes < 1024 {
+        return bytes.to_string();
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    loop {
+        // round up first, as 9.99K is shown as 10K and 1023.9K as 1.0M
+        let tenths = (size * 10.0).ceil() / 10.0;
+        let rounded = if tenths < 10.0 { tenths } else { size.ceil() };
+        if rounded >= 1024.0 && unit + 1 < UNITS.len() {
+            size /= 1024.0;
+            unit += 1;
+        } else if rounded < 10.0 {
+            return format!("{rounded:.1}{}", UNITS[unit]);
+        } else {
+            return format!("{rounded}{}", UNITS[unit]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sizes_and_printers() -> Result<()> {
+        let mut t = FsTree::new();
+        let a = t.add_dir(FsTree::ROOT, "a")?;
+        let e = t.add_dir(a, "e")?;
+        // a directory holding only a directory still counts
+        let only = t.add_dir(FsTree::ROOT, "only")?;
+        let inner = t.add_dir(only, "inner")?;
+        t.add_file(e, "i", 584)?;
+        t.add_file(a, "f", 29116)?;
+        t.add_file(inner, "big", 3 << 20)?;
+        t.add_file(FsTree::ROOT, "b.txt", 14848514)?;
+        assert!(t.add_file(a, "f", 1).is_err());
+        assert!(t.add_dir(a, "f").is_err());
+
+        let sizes = t.sizes();
+        assert_eq!(sizes[e], 584);
+        assert_eq!(sizes[a], 29700);
+        assert_eq!(sizes[only], 3 << 20);
+        assert_eq!(t.size(FsTree::ROOT), sizes[FsTree::ROOT]);
+        assert_eq!(t.path(e), "/a/e/");
+
+        assert_eq!(t.du(Some(1)), "30K\t/a/\n3.0M\t/only/\n18M\t/\n");
+        for (bytes, shown) in [
+            (1023, "1023"),
+            (1024, "1.0K"),
+            (10137, "9.9K"),
+            (10239, "10K"),
+            (10240, "10K"),
+            (1048575, "1.0M"),
+            (1048576, "1.0M"),
+            (10 << 20, "10M"),
+        ] {
+            assert_eq!(human_size(bytes), shown, "{bytes}");
+        }
+        assert_eq!(
+            t.tree(Some(1)),
+            "[  18M]  /\n├── [  30K]  a\n├── [  15M]  b.txt\n└── [ 3.0M]  only\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,262 @@
+//! Writing out the per-tree results as spreadsheets and pictures
+use crate::forest::Views;
+use std::fmt::Write;
+
+/// One line per row of trees, comma-separated
+fn csv<T: std::fmt::Display>(width: usize, values: &[T]) -> String {
+    let mut out = String::new();
+    for row in values.chunks(width.max(1)) {
+        let cells: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+        let _ = writeln!(out, "{}", cells.join(","));
+    }
+    out
+}
+
+pub fn scores_csv(views: &Views) -> String {
+    csv(views.width, &views.scenic_scores())
+}
+
+/// 1 for visible from outside, 0 otherwise
+pub fn visible_csv(views: &Views) -> String {
+    let mask: Vec<u8> = views.visible.iter().map(|v| *v as u8).collect();
+    csv(views.width, &mask)
+}
+
+/// Scenic scores as greys, brightest best
+///
+/// Scores span several orders of magnitude, so the scale is logarithmic:
+/// otherwise everything but the very best spot would be black.
+pub fn heatmap(views: &Views) -> Vec<u8> {
+    let scores = views.scenic_scores();
+    let top = (*scores.iter().max().unwrap_or(&0) as f64).ln_1p();
+    scores
+        .iter()
+        .map(|s| {
+            if top > 0.0 {
+                (255.0 * (*s as f64).ln_1p() / top).round() as u8
+            } else {
+                0
+            }
+        })
+        .collect()
+}
+
+/// White where a tree is visible from outside, black where it's hidden
+pub fn mask(views: &Views) -> Vec<u8> {
+    views
+        .visible
+        .iter()
+        .map(|v| if *v { 255 } else { 0 })
+        .collect()
+}
+
+/// Binary greyscale PGM
+pub fn pgm(width: usize, height: usize, pixels: &[u8]) -> Vec<u8> {
+    let mut out = format!("P5\n{width} {height}\n255\n").into_bytes();
+    out.extend_from_slice(pixels);
+    out
+}
+
+/// 8-bit greyscale PNG, stored without compression
+pub fn png(width: usize, height: usize, pixels: &[u8]) -> Vec<u8> {
+    // each scanline gets a leading filter-type byte (0: none)
+    let mut raw = Vec::with_capacity((width + 1) * height);
+    for row in pixels.chunks(width.max(1)).take(height) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    // zlib stream made of "stored" deflate blocks
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none() as u8;
+        let len = block.len() as u16;
+        zlib.push(last);
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // bit depth 8, colour type 0 (grey), default compression, filter and no interlace
+    header.extend_from_slice(&[8, 0, 0, 0, 0]);
+
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+    chunk(&mut out, b"IHDR", &header);
+    chunk(&mut out, b"IDAT", &zlib);
+    chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &x in chunk {
+            a += x as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::forest::Forest;
+
+    const SAMPLE: &str = "30373\n25512\n65332\n33549\n35390\n";
+
+    fn parse_csv(text: &str) -> Vec<Vec<usize>> {
+        text.lines()
+            .map(|l| l.split(',').map(|c| c.parse().unwrap()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn csvs() -> anyhow::Result<()> {
+        let views = Forest::parse(SAMPLE)?.views();
+
+        let scores = parse_csv(&scores_csv(&views));
+        assert_eq!(scores.len(), 5);
+        assert!(scores.iter().all(|r| r.len() == 5));
+        assert_eq!(scores.concat(), views.scenic_scores());
+        // the puzzle's best spot
+        assert_eq!(scores[3][2], 8);
+
+        let visible = parse_csv(&visible_csv(&views));
+        assert_eq!(visible.concat().iter().sum::<usize>(), 21);
+        assert_eq!(visible[1], [1, 1, 1, 0, 1]);
+        assert_eq!(visible[2], [1, 1, 0, 1, 1]);
+        Ok(())
+    }
+
+    #[test]
+    fn pgm_heatmap() -> anyhow::Result<()> {
+        let views = Forest::parse(SAMPLE)?.views();
+        let image = pgm(5, 5, &heatmap(&views));
+        let header = b"P5\n5 5\n255\n";
+        assert!(image.starts_with(header));
+        let pixels = &image[header.len()..];
+        assert_eq!(pixels.len(), 25);
+        // the best score is white, and the edges, which all score 0, are black
+        assert_eq!(pixels[3 * 5 + 2], 255);
+        assert!(pixels.iter().filter(|p| **p == 255).count() == 1);
+        assert!(pixels[..5].iter().all(|p| *p == 0));
+        // 4 is the next best, and about two thirds as bright on the log scale
+        assert_eq!(pixels[5 + 2], (255.0 * 5f64.ln() / 9f64.ln()).round() as u8);
+        Ok(())
+    }
+
+    /// Check each chunk's CRC, and return the chunks
+    fn read_chunks(png: &[u8]) -> Vec<(String, &[u8])> {
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        let mut out = vec![];
+        let mut rest = &png[8..];
+        while !rest.is_empty() {
+            let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let (body, crc) = rest[4..].split_at(4 + len);
+            assert_eq!(
+                crc32(body),
+                u32::from_be_bytes(crc[..4].try_into().unwrap())
+            );
+            out.push((String::from_utf8(body[..4].to_vec()).unwrap(), &body[4..]));
+            rest = &crc[4..];
+        }
+        out
+    }
+
+    /// Undo the stored deflate blocks
+    fn inflate_stored(zlib: &[u8]) -> Vec<u8> {
+        assert_eq!(&zlib[..2], [0x78, 0x01]);
+        let mut out = vec![];
+        let mut rest = &zlib[2..];
+        loop {
+            let last = rest[0] == 1;
+            let len = u16::from_le_bytes([rest[1], rest[2]]);
+            assert_eq!(!len, u16::from_le_bytes([rest[3], rest[4]]));
+            out.extend_from_slice(&rest[5..5 + len as usize]);
+            rest = &rest[5 + len as usize..];
+            if last {
+                break;
+            }
+        }
+        assert_eq!(rest, adler32(&out).to_be_bytes());
+        out
+    }
+
+    #[test]
+    fn png_structure() -> anyhow::Result<()> {
+        let views = Forest::parse(SAMPLE)?.views();
+        let pixels = mask(&views);
+        let image = png(5, 5, &pixels);
+        let chunks = read_chunks(&image);
+        let kinds: Vec<&str> = chunks.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(kinds, ["IHDR", "IDAT", "IEND"]);
+
+        let ihdr = chunks[0].1;
+        assert_eq!(ihdr.len(), 13);
+        assert_eq!(&ihdr[..4], 5u32.to_be_bytes());
+        assert_eq!(&ihdr[4..8], 5u32.to_be_bytes());
+        assert_eq!(&ihdr[8..], [8, 0, 0, 0, 0]);
+
+        // every scanline starts with filter type 0
+        let raw = inflate_stored(chunks[1].1);
+        assert_eq!(raw.len(), 5 * 6);
+        for (row, line) in raw.chunks(6).zip(pixels.chunks(5)) {
+            assert_eq!(row[0], 0);
+            assert_eq!(&row[1..], line);
+        }
+        assert!(chunks[2].1.is_empty());
+
+        // big enough to need more than one stored block
+        let (w, h) = (300, 250);
+        let pixels: Vec<u8> = (0..w * h).map(|i| (i % 251) as u8).collect();
+        let image = png(w, h, &pixels);
+        let chunks = read_chunks(&image);
+        assert_eq!(&chunks[0].1[..8], [0, 0, 1, 44, 0, 0, 0, 250]);
+        let raw = inflate_stored(chunks[1].1);
+        assert_eq!(raw.len(), (w + 1) * h);
+        assert_eq!(&raw[w + 2..2 * w + 2], &pixels[w..2 * w]);
+        Ok(())
+    }
+
+    #[test]
+    fn checksums() {
+        // known values for the ASCII string
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+}
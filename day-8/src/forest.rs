@@ -0,0 +1,196 @@
+//! Dense grid of tree heights, with every tree's views worked out in linear time
+use anyhow::{bail, Result};
+
+/// Tree heights, row-major with (0,0) at northwest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Forest {
+    pub width: usize,
+    pub height: usize,
+    heights: Vec<i8>,
+}
+
+impl Forest {
+    /// Rows must all be the same length
+    pub fn from_rows(rows: &[Vec<i8>]) -> Result<Self> {
+        let width = rows.first().map_or(0, Vec::len);
+        if let Some((i, r)) = rows.iter().enumerate().find(|(_, r)| r.len() != width) {
+            bail!(
+                "Row {} has {} trees but row 1 has {width}; the forest must be rectangular",
+                i + 1,
+                r.len()
+            );
+        }
+        Ok(Forest {
+            width,
+            height: rows.len(),
+            heights: rows.concat(),
+        })
+    }
+
+    /// One row of digits per line; anything else is an error,
+    /// bar blank lines at the end
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut lines: Vec<&str> = input.lines().collect();
+        while lines.last().is_some_and(|l| l.trim().is_empty()) {
+            lines.pop();
+        }
+        let rows = lines
+            .into_iter()
+            .enumerate()
+            .map(|(r, line)| {
+                line.trim_end_matches('\r')
+                    .chars()
+                    .enumerate()
+                    .map(|(c, ch)| match ch.to_digit(10) {
+                        Some(d) => Ok(d as i8),
+                        None => bail!(
+                            "Row {} column {}: {ch:?} is not a tree height",
+                            r + 1,
+                            c + 1
+                        ),
+                    })
+                    .collect::<Result<Vec<i8>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Self::from_rows(&rows)
+    }
+
+    fn idx(&self, row: usize, col: usize) -> usize {
+        row * self.width + col
+    }
+
+    /// Look in all four directions from every tree at once
+    pub fn views(&self) -> Views {
+        let n = self.heights.len();
+        let mut views = Views {
+            width: self.width,
+            height: self.height,
+            visible: vec![false; n],
+            distance: [vec![0; n], vec![0; n], vec![0; n], vec![0; n]],
+        };
+
+        // Stacks are strictly shrinking, so no deeper than the number of distinct heights
+        let depth = self.heights.iter().max().zip(self.heights.iter().min());
+        let depth = depth.map_or(0, |(hi, lo)| (*hi as i16 - *lo as i16) as usize + 1);
+
+        // Along each row, both ways
+        let mut stack = Stack::new(1, depth);
+        for row in 0..self.height {
+            let start = self.idx(row, 0);
+            let cells = start..start + self.width;
+            self.sweep(cells.clone(), WEST, &mut views, &mut stack);
+            self.sweep(cells.rev(), EAST, &mut views, &mut stack);
+        }
+
+        // Down and up the columns, a whole row at a time to stay in cache
+        let mut stacks = Stack::new(self.width, depth);
+        for (dir, rows) in [(NORTH, 0..self.height), (SOUTH, 0..self.height)] {
+            stacks.clear();
+            for k in rows {
+                let row = if dir == NORTH { k } else { self.height - 1 - k };
+                for col in 0..self.width {
+                    let i = self.idx(row, col);
+                    let (d, seen) = stacks.look_back(col, k, self.heights[i]);
+                    views.distance[dir][i] = d;
+                    views.visible[i] |= seen;
+                }
+            }
+        }
+
+        views
+    }
+
+    fn sweep(
+        &self,
+        cells: impl Iterator<Item = usize>,
+        dir: usize,
+        views: &mut Views,
+        stack: &mut Stack,
+    ) {
+        stack.clear();
+        for (k, i) in cells.enumerate() {
+            let (d, seen) = stack.look_back(0, k, self.heights[i]);
+            views.distance[dir][i] = d;
+            views.visible[i] |= seen;
+        }
+    }
+}
+
+/// Several monotonic stacks of (position, height) laid out flat
+///
+/// Each holds the trees that could still block the view of a later one,
+/// strictly shrinking towards the top.
+struct Stack {
+    depth: usize,
+    items: Vec<(u32, i8)>,
+    lens: Vec<usize>,
+}
+
+impl Stack {
+    fn new(count: usize, depth: usize) -> Self {
+        Stack {
+            depth,
+            items: vec![(0, 0); count * depth],
+            lens: vec![0; count],
+        }
+    }
+
+    fn clear(&mut self) {
+        self.lens.iter_mut().for_each(|l| *l = 0);
+    }
+
+    /// One step along a line of trees, looking back towards where we started
+    ///
+    /// A new tree pops everything shorter than itself; whatever is left on top
+    /// is the first tree that stops its view, and if nothing is left it can see
+    /// the edge. A tree of equal height then gives way to the new one, which is
+    /// nearer. Returns the viewing distance and whether it's visible from the edge.
+    fn look_back(&mut self, which: usize, k: usize, h: i8) -> (u32, bool) {
+        let stack = &mut self.items[which * self.depth..(which + 1) * self.depth];
+        let len = &mut self.lens[which];
+        while *len > 0 && stack[*len - 1].1 < h {
+            *len -= 1;
+        }
+        let out = if *len > 0 {
+            let (p, t) = stack[*len - 1];
+            if t == h {
+                *len -= 1;
+            }
+            (k as u32 - p, false)
+        } else {
+            (k as u32, true)
+        };
+        stack[*len] = (k as u32, h);
+        *len += 1;
+        out
+    }
+}
+
+pub const NORTH: usize = 0;
+pub const SOUTH: usize = 1;
+pub const WEST: usize = 2;
+pub const EAST: usize = 3;
+
+/// What every tree can see, row-major like the forest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Views {
+    pub width: usize,
+    pub height: usize,
+    /// Visible from outside the grid in at least one direction
+    pub visible: Vec<bool>,
+    /// Viewing distance looking [`NORTH`], [`SOUTH`], [`WEST`] and [`EAST`]
+    pub distance: [Vec<u32>; 4],
+}
+
+impl Views {
+    pub fn visible_count(&self) -> usize {
+        self.visible.iter().filter(|v| **v).count()
+    }
+
+    /// Product of the four viewing distances, for every tree
+    pub fn scenic_scores(&self) -> Vec<usize> {
+        (0..self.visible.len())
+            .map(|i| self.distance.iter().map(|d| d[i] as usize).product())
+            .collect()
+    }
+}
@@ -1,184 +1,314 @@
-use std::{collections::HashSet, io};
+mod export;
+mod forest;
 
-fn main() -> io::Result<()> {
-    const RADIX: u32 = 10;
+use anyhow::Result;
+use forest::Forest;
+use std::io::{self, Read};
 
-    let grid: Vec<Vec<i8>> = io::stdin()
-        .lines()
-        .filter_map(|x| x.ok())
-        .map(|s| {
-            s.chars()
-                .filter_map(|n| n.to_digit(RADIX))
-                .map(|n| n as i8)
-                .collect()
-        })
-        .collect();
+/// Given a path prefix as an argument, also write out the per-tree
+/// scores and visibility as `<prefix>-scores.csv`, `<prefix>-visible.csv`
+/// and greyscale `.pgm` and `.png` pictures of each
+fn main() -> Result<()> {
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+    let forest = Forest::parse(&input)?;
 
-    println!("Part A {}", part_a(&grid));
+    let views = forest.views();
 
-    println!("Part B {}", part_b(&grid));
+    println!("Part A {}", views.visible_count());
+
+    println!(
+        "Part B {}",
+        views.scenic_scores().into_iter().max().unwrap_or(0)
+    );
+
+    if let Some(prefix) = std::env::args().nth(1) {
+        let (w, h) = (views.width, views.height);
+        std::fs::write(format!("{prefix}-scores.csv"), export::scores_csv(&views))?;
+        std::fs::write(format!("{prefix}-visible.csv"), export::visible_csv(&views))?;
+        for (name, pixels) in [
+            ("scores", export::heatmap(&views)),
+            ("visible", export::mask(&views)),
+        ] {
+            std::fs::write(format!("{prefix}-{name}.pgm"), export::pgm(w, h, &pixels))?;
+            std::fs::write(format!("{prefix}-{name}.png"), export::png(w, h, &pixels))?;
+        }
+    }
 
     Ok(())
 }
 
-/// Treetop tree house
-/// Part A:
-/// Count the number of trees that are visible from outside the grid when looking directly along a row or column.
-/// A tree is visible if all of the other trees between it and an edge of the grid are shorter than it. Only consider trees in the same row or column; that is, only look up, down, left, or right from any given tree.
-/// Each tree is represented as a single digit whose value is its height, where 0 is the shortest and 9 is the tallest.
-/// Every edge tree is visible as well.
-/// Part A: how many trees are visible from outside the grid?
-/// Need to find peaks running north, south, east and west along grid
-fn part_a(grid: &Vec<Vec<i8>>) -> usize {
-    // bounds:
-    let width = grid.iter().map(|s| s.len()).max().unwrap();
-    let height = grid.len();
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
 
-    // Grid is indexed row-major with (0,0) at northwest
+    // the original solutions, kept to check Forest against
 
-    // Complexity: trees can be of height zero
-    // Luckily, all tree heights easily fit in an i8 so we can use -1
+    /// Treetop tree house
+    /// Part A:
+    /// Count the number of trees that are visible from outside the grid when looking directly along a row or column.
+    /// A tree is visible if all of the other trees between it and an edge of the grid are shorter than it. Only consider trees in the same row or column; that is, only look up, down, left, or right from any given tree.
+    /// Each tree is represented as a single digit whose value is its height, where 0 is the shortest and 9 is the tallest.
+    /// Every edge tree is visible as well.
+    /// Part A: how many trees are visible from outside the grid?
+    /// Need to find peaks running north, south, east and west along grid
+    #[allow(clippy::needless_range_loop)]
+    fn part_a(grid: &[Vec<i8>]) -> usize {
+        // bounds:
+        let width = grid.iter().map(|s| s.len()).max().unwrap_or(0);
+        let height = grid.len();
 
-    // Complexity: double-counting trees
-    // Simple solution: use a HashSet
+        // Grid is indexed row-major with (0,0) at northwest
 
-    let mut tall_trees: HashSet<(usize, usize)> = HashSet::new();
+        // Complexity: trees can be of height zero
+        // Luckily, all tree heights easily fit in an i8 so we can use -1
 
-    let mut max_so_far;
+        // Complexity: double-counting trees
+        // Simple solution: use a HashSet
 
-    // West to East
-    for row in 0..height {
-        // reset: west to east is by rows
-        max_so_far = -1;
-        for col in 0..width {
-            if grid[row][col] > max_so_far {
-                tall_trees.insert((row, col));
-                max_so_far = grid[row][col];
+        let mut tall_trees: HashSet<(usize, usize)> = HashSet::new();
+
+        let mut max_so_far;
+
+        // West to East
+        for row in 0..height {
+            // reset: west to east is by rows
+            max_so_far = -1;
+            for col in 0..width {
+                if grid[row][col] > max_so_far {
+                    tall_trees.insert((row, col));
+                    max_so_far = grid[row][col];
+                }
             }
         }
-    }
 
-    // East to West
-    for row in 0..height {
-        // reset: east to west is by rows
-        max_so_far = -1;
-        for col in 1..width {
-            // reversal
-            let col = width - col;
-            if grid[row][col] > max_so_far {
-                tall_trees.insert((row, col));
-                max_so_far = grid[row][col];
+        // East to West
+        for row in 0..height {
+            // reset: east to west is by rows
+            max_so_far = -1;
+            for col in 1..width {
+                // reversal
+                let col = width - col;
+                if grid[row][col] > max_so_far {
+                    tall_trees.insert((row, col));
+                    max_so_far = grid[row][col];
+                }
             }
         }
-    }
 
-    // North to South
-    for col in 0..width {
-        // reset: north to south is by cols
-        max_so_far = -1;
-        for row in 0..height {
-            if grid[row][col] > max_so_far {
-                tall_trees.insert((row, col));
-                max_so_far = grid[row][col];
+        // North to South
+        for col in 0..width {
+            // reset: north to south is by cols
+            max_so_far = -1;
+            for row in 0..height {
+                if grid[row][col] > max_so_far {
+                    tall_trees.insert((row, col));
+                    max_so_far = grid[row][col];
+                }
             }
         }
-    }
 
-    // South to North
-    for col in 0..width {
-        // reset: south to north is by cols
-        max_so_far = -1;
-        for row in 1..height {
-            let row = height - row;
-            if grid[row][col] > max_so_far {
-                tall_trees.insert((row, col));
-                max_so_far = grid[row][col];
+        // South to North
+        for col in 0..width {
+            // reset: south to north is by cols
+            max_so_far = -1;
+            for row in 1..height {
+                let row = height - row;
+                if grid[row][col] > max_so_far {
+                    tall_trees.insert((row, col));
+                    max_so_far = grid[row][col];
+                }
             }
         }
+
+        tall_trees.len()
     }
 
-    tall_trees.len()
-}
+    /// Part B:
+    /// Content with the amount of tree cover available, the Elves just need to know the best spot to build their tree house: they would like to be able to see a lot of trees.
+    /// To measure the viewing distance from a given tree, look up, down, left, and right from that tree; stop if you reach an edge or at the first tree that is the same height
+    /// or taller than the tree under consideration. (If a tree is right on the edge, at least one of its viewing distances will be zero.)
+    /// A tree's scenic score is found by multiplying together its viewing distance in each of the four directions. For this tree, this is 4 (found by multiplying 1 * 1 * 2 * 2).
+    /// Question: what is the highest scenic score possible for any tree?
+    #[allow(clippy::needless_range_loop)]
+    fn part_b(grid: &[Vec<i8>]) -> usize {
+        // OK so
+        //  for each tree:
+        //      for each direction:
+        //          measure distance to edge or equal/taller tree
+        //      multiply distances
+        // n.b. edge trees have a score of zero so we don't need to consider them as sources
+
+        // bounds:
+        let width = grid.iter().map(|s| s.len()).max().unwrap_or(0);
+        let height = grid.len();
+
+        let mut max_score = 0;
 
-/// Part B:
-/// Content with the amount of tree cover available, the Elves just need to know the best spot to build their tree house: they would like to be able to see a lot of trees.
-/// To measure the viewing distance from a given tree, look up, down, left, and right from that tree; stop if you reach an edge or at the first tree that is the same height
-/// or taller than the tree under consideration. (If a tree is right on the edge, at least one of its viewing distances will be zero.)
-/// A tree's scenic score is found by multiplying together its viewing distance in each of the four directions. For this tree, this is 4 (found by multiplying 1 * 1 * 2 * 2).
-/// Question: what is the highest scenic score possible for any tree?
-fn part_b(grid: &Vec<Vec<i8>>) -> usize {
-    // OK so
-    //  for each tree:
-    //      for each direction:
-    //          measure distance to edge or equal/taller tree
-    //      multiply distances
-    // n.b. edge trees have a score of zero so we don't need to consider them as sources
-
-    // bounds:
-    let width = grid.iter().map(|s| s.len()).max().unwrap();
-    let height = grid.len();
-
-    let mut max_score = 0;
-
-    for row in 1..(height - 1) {
-        for col in 1..(width - 1) {
-            let this = grid[row][col];
-            // eprintln!("row: {row} col: {col} this: {this}");
-            let mut score = 1;
-
-            // South to North
-            for d in 1..=row {
-                let r = row - d;
-                let that = grid[r][col];
-
-                if that >= this || r == 0 {
-                    // eprintln!("\tr: {r} col: {col} that: {that}");
-                    score *= d;
-                    break;
+        for row in 1..height.saturating_sub(1) {
+            for col in 1..width.saturating_sub(1) {
+                let this = grid[row][col];
+                // eprintln!("row: {row} col: {col} this: {this}");
+                let mut score = 1;
+
+                // South to North
+                for d in 1..=row {
+                    let r = row - d;
+                    let that = grid[r][col];
+
+                    if that >= this || r == 0 {
+                        // eprintln!("\tr: {r} col: {col} that: {that}");
+                        score *= d;
+                        break;
+                    }
                 }
-            }
-            // North to South
-            for d in 1..(height - row) {
-                let r = row + d;
-                let that = grid[r][col];
-
-                if that >= this || r == height - 1 {
-                    // eprintln!("\tr: {r} col: {col} that: {that}");
-                    score *= d;
-                    break;
+                // North to South
+                for d in 1..(height - row) {
+                    let r = row + d;
+                    let that = grid[r][col];
+
+                    if that >= this || r == height - 1 {
+                        // eprintln!("\tr: {r} col: {col} that: {that}");
+                        score *= d;
+                        break;
+                    }
                 }
-            }
 
-            // East to West
-            for d in 1..=col {
-                let c = col - d;
-                let that = grid[row][c];
+                // East to West
+                for d in 1..=col {
+                    let c = col - d;
+                    let that = grid[row][c];
 
-                if that >= this || c == 0 {
-                    // eprintln!("\trow: {row} c: {c} that: {that}");
-                    score *= d;
-                    break;
+                    if that >= this || c == 0 {
+                        // eprintln!("\trow: {row} c: {c} that: {that}");
+                        score *= d;
+                        break;
+                    }
                 }
-            }
 
-            // West to East
-            for d in 1..(width - col) {
-                let c = col + d;
-                let that = grid[row][c];
+                // West to East
+                for d in 1..(width - col) {
+                    let c = col + d;
+                    let that = grid[row][c];
 
-                if that >= this || c == width - 1 {
-                    // eprintln!("\trow: {row} c: {c} that: {that}");
-                    score *= d;
-                    break;
+                    if that >= this || c == width - 1 {
+                        // eprintln!("\trow: {row} c: {c} that: {that}");
+                        score *= d;
+                        break;
+                    }
                 }
+
+                max_score = max_score.max(score);
             }
+        }
+
+        max_score
+    }
+
+    /// Heights 0-9 via xorshift
+    fn random_grid(width: usize, height: usize, seed: u64) -> Vec<Vec<i8>> {
+        let mut x = seed.max(1);
+        (0..height)
+            .map(|_| {
+                (0..width)
+                    .map(|_| {
+                        x ^= x << 13;
+                        x ^= x >> 7;
+                        x ^= x << 17;
+                        (x % 10) as i8
+                    })
+                    .collect()
+            })
+            .collect()
+    }
 
-            if score > max_score {
-                eprintln!("row: {row} col: {col} this: {this} score: {score}");
-                max_score = score;
+    #[test]
+    fn sample() {
+        let views = Forest::parse("30373\n25512\n65332\n33549\n35390\n")
+            .unwrap()
+            .views();
+        assert_eq!(views.visible_count(), 21);
+        // the middle 5 in the second row
+        assert_eq!(views.scenic_scores()[7], 4);
+        assert_eq!(views.scenic_scores().into_iter().max(), Some(8));
+    }
+
+    #[test]
+    fn degenerate_forests() -> Result<()> {
+        for height in 0..=2 {
+            for width in [0, 1, 2, 5] {
+                let grid = random_grid(width, height, 7);
+                let views = Forest::from_rows(&grid)?.views();
+                // with no interior, everything is on an edge
+                assert_eq!(views.visible_count(), width * height);
+                assert!(views.scenic_scores().iter().all(|s| *s == 0));
+                assert_eq!(part_a(&grid), width * height);
+                assert_eq!(part_b(&grid), 0);
             }
         }
+        let column = Forest::parse("1\n2\n3\n4\n")?.views();
+        assert_eq!(column.visible_count(), 4);
+        assert_eq!(column.scenic_scores(), vec![0; 4]);
+        assert_eq!(Forest::parse("")?.views().visible_count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn bad_forests() {
+        let ragged = Forest::parse("123\n45\n678\n").unwrap_err();
+        assert!(ragged
+            .to_string()
+            .starts_with("Row 2 has 2 trees but row 1 has 3"));
+        let blank = Forest::parse("123\n\n678\n").unwrap_err();
+        assert!(blank.to_string().starts_with("Row 2 has 0 trees"));
+        // but blank lines at the end are just the end of the file
+        let trailing = Forest::parse("123\n456\n\n\r\n").unwrap();
+        assert_eq!((trailing.width, trailing.height), (3, 2));
+        let junk = Forest::parse("123\n4x6\n").unwrap_err();
+        assert_eq!(junk.to_string(), "Row 2 column 2: 'x' is not a tree height");
     }
 
-    max_score
+    #[test]
+    fn matches_direct_search() {
+        for seed in 1..20 {
+            let grid = random_grid(3 + seed as usize, 20 - seed as usize, seed);
+            let views = Forest::from_rows(&grid).unwrap().views();
+            assert_eq!(views.visible_count(), part_a(&grid));
+            assert_eq!(views.scenic_scores().into_iter().max(), Some(part_b(&grid)));
+        }
+    }
+
+    /// Run with `cargo test --release -- --ignored --nocapture`
+    ///
+    /// With random digits every view is short, so the direct search is
+    /// effectively linear too; the staircase (heights rising 0-99 diagonally)
+    /// gives long views, which is where walking outward from each tree hurts.
+    #[test]
+    #[ignore]
+    fn bench_large_forest() {
+        use std::time::Instant;
+        let staircase: Vec<Vec<i8>> = (0..5000)
+            .map(|r| (0..5000).map(|c| ((r + c) % 100) as i8).collect())
+            .collect();
+        for (name, grid) in [
+            ("random", random_grid(5000, 5000, 42)),
+            ("staircase", staircase),
+        ] {
+            let t = Instant::now();
+            let views = Forest::from_rows(&grid).unwrap().views();
+            let (a, b) = (
+                views.visible_count(),
+                views.scenic_scores().into_iter().max(),
+            );
+            let t_stacks = t.elapsed();
+
+            let t = Instant::now();
+            let (a_old, b_old) = (part_a(&grid), part_b(&grid));
+            let t_direct = t.elapsed();
+
+            assert_eq!((a, b), (a_old, Some(b_old)));
+            println!("5000x5000 {name}: monotonic stacks {t_stacks:?}, direct search {t_direct:?}");
+        }
+    }
 }
@@ -0,0 +1,495 @@
+//! Recording the rope step by step and playing it back as text, a cast or a GIF
+use crate::rope::{Move, Position, Rope};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+/// Where every knot was after one step of the head
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// Which line of the input was being carried out (from 0)
+    pub move_index: usize,
+    pub knots: Vec<Position>,
+}
+
+impl Frame {
+    /// The first knot that's come apart from the one ahead of it, if any
+    ///
+    /// This can't happen with the follow rule as it stands, but it's
+    /// exactly what went wrong with the old one, so keep checking.
+    pub fn detached(&self) -> Option<usize> {
+        self.knots
+            .windows(2)
+            .position(|w| (w[0].x - w[1].x).abs() > 1 || (w[0].y - w[1].y).abs() > 1)
+            .map(|k| k + 1)
+    }
+}
+
+/// Every frame of a run, starting with everything at the origin
+#[derive(Debug, Clone, Default)]
+pub struct Recording {
+    pub frames: Vec<Frame>,
+}
+
+impl Recording {
+    pub fn record(moves: &[Move], knot_count: usize) -> Self {
+        let mut rope = Rope::new(knot_count);
+        let mut frames = vec![Frame {
+            move_index: 0,
+            knots: rope.knots().to_vec(),
+        }];
+        for (i, m) in moves.iter().enumerate() {
+            for s in m.steps() {
+                rope.step(s);
+                frames.push(Frame {
+                    move_index: i,
+                    knots: rope.knots().to_vec(),
+                });
+            }
+        }
+        Recording { frames }
+    }
+
+    /// The first frame where the rope came apart, and the knot that did it
+    pub fn first_detached(&self) -> Option<(usize, usize)> {
+        self.frames
+            .iter()
+            .enumerate()
+            .find_map(|(i, f)| f.detached().map(|k| (i, k)))
+    }
+
+    /// Just the frames up to and including `end`, to look at what led up to it
+    pub fn until(&self, end: usize) -> Self {
+        Recording {
+            frames: self.frames[..=end.min(self.frames.len().saturating_sub(1))].to_vec(),
+        }
+    }
+
+    /// Go through the frames with the viewport's corner and the tail's trail so far
+    fn scenes(
+        &self,
+        view: &Viewport,
+        mut draw: impl FnMut(usize, &Frame, Position, &HashSet<Position>),
+    ) {
+        let mut origin = None;
+        let mut trail = HashSet::new();
+        for (i, f) in self.frames.iter().enumerate() {
+            let o = view.follow(origin, f.knots[0]);
+            origin = Some(o);
+            trail.insert(f.knots[f.knots.len() - 1]);
+            draw(i, f, o, &trail);
+        }
+    }
+
+    /// One text picture per frame, each with a heading line
+    pub fn text(&self, view: &Viewport) -> String {
+        let mut out = String::new();
+        self.scenes(view, |i, f, origin, trail| {
+            let _ = writeln!(out, "{}", heading(i, f));
+            for row in view.rows(f, origin, trail) {
+                let _ = writeln!(out, "{row}");
+            }
+            let _ = writeln!(out);
+        });
+        out
+    }
+
+    /// An asciinema (v2) cast, `delay` seconds per frame
+    pub fn cast(&self, view: &Viewport, delay: f64) -> String {
+        let mut out = format!(
+            "{{\"version\": 2, \"width\": {}, \"height\": {}, \"title\": \"Rope Bridge\"}}\n",
+            view.width,
+            view.height + 1
+        );
+        self.scenes(view, |i, f, origin, trail| {
+            // clear the screen and start again from the top
+            let mut screen = format!("\x1b[H\x1b[2J{}", heading(i, f));
+            for row in view.rows(f, origin, trail) {
+                screen.push_str("\r\n");
+                screen.push_str(&row);
+            }
+            let _ = writeln!(
+                out,
+                "[{:.3}, \"o\", \"{}\"]",
+                i as f64 * delay,
+                json_escape(&screen)
+            );
+        });
+        out
+    }
+
+    /// An endlessly looping GIF, `scale` pixels to a cell and `delay` hundredths of a second per frame
+    pub fn gif(&self, view: &Viewport, scale: usize, delay: u16) -> Vec<u8> {
+        let scale = scale.max(1);
+        let (w, h) = (view.width * scale, view.height * scale);
+
+        let mut out = b"GIF89a".to_vec();
+        out.extend_from_slice(&(w as u16).to_le_bytes());
+        out.extend_from_slice(&(h as u16).to_le_bytes());
+        // global colour table of 4 entries, background colour 0, square pixels
+        out.extend_from_slice(&[0b1000_0001, 0, 0]);
+        out.extend_from_slice(&PALETTE);
+        // loop forever
+        out.extend_from_slice(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00");
+
+        self.scenes(view, |_, f, origin, trail| {
+            let cells = view.cells(f, origin, trail);
+            let mut pixels = Vec::with_capacity(w * h);
+            for row in cells.chunks(view.width) {
+                let line: Vec<u8> = row
+                    .iter()
+                    .flat_map(|c| std::iter::repeat_n(*c as u8, scale))
+                    .collect();
+                for _ in 0..scale {
+                    pixels.extend_from_slice(&line);
+                }
+            }
+
+            // graphic control: just the delay
+            out.extend_from_slice(&[0x21, 0xf9, 4, 0]);
+            out.extend_from_slice(&delay.to_le_bytes());
+            out.extend_from_slice(&[0, 0]);
+            // image descriptor covering the whole picture
+            out.push(0x2c);
+            out.extend_from_slice(&[0, 0, 0, 0]);
+            out.extend_from_slice(&(w as u16).to_le_bytes());
+            out.extend_from_slice(&(h as u16).to_le_bytes());
+            out.push(0);
+            out.push(MIN_CODE_SIZE);
+            for block in lzw(&pixels).chunks(255) {
+                out.push(block.len() as u8);
+                out.extend_from_slice(block);
+            }
+            out.push(0);
+        });
+        out.push(0x3b);
+        out
+    }
+}
+
+fn heading(i: usize, f: &Frame) -> String {
+    format!(
+        "step {i}, move {}, head at {}",
+        f.move_index + 1,
+        f.knots[0]
+    )
+}
+
+fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\r' => out.push_str("\\r"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// What's in a cell of the picture, which doubles as its GIF colour
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Cell {
+    Empty = 0,
+    Trail = 1,
+    Knot = 2,
+    Head = 3,
+}
+
+const PALETTE: [u8; 12] = [
+    0x10, 0x10, 0x18, // empty
+    0x50, 0x50, 0x68, // tail's trail
+    0xe0, 0xc0, 0x40, // knot
+    0xf0, 0x40, 0x30, // head
+];
+
+/// A window onto the rope that moves along with the head
+///
+/// The head is kept at least `margin` cells from the edges; the window only
+/// moves when the head would get any closer than that.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Viewport {
+    pub width: usize,
+    pub height: usize,
+    pub margin: usize,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Viewport {
+            width: 40,
+            height: 20,
+            margin: 5,
+        }
+    }
+}
+
+impl Viewport {
+    /// Where the bottom left corner goes next, given where it was
+    fn follow(&self, origin: Option<Position>, head: Position) -> Position {
+        let Some(mut o) = origin else {
+            // start centred on the head
+            return Position {
+                x: head.x - self.width as isize / 2,
+                y: head.y - self.height as isize / 2,
+            };
+        };
+        let along = |o: &mut isize, h: isize, size: usize| {
+            let margin = self.margin.min(size.saturating_sub(1) / 2) as isize;
+            let far = size as isize - 1 - margin;
+            if h < *o + margin {
+                *o = h - margin;
+            } else if h > *o + far {
+                *o = h - far;
+            }
+        };
+        along(&mut o.x, head.x, self.width);
+        along(&mut o.y, head.y, self.height);
+        o
+    }
+
+    /// Row-major from the top left, so north is up
+    fn cells(&self, f: &Frame, origin: Position, trail: &HashSet<Position>) -> Vec<Cell> {
+        let mut cells = vec![Cell::Empty; self.width * self.height];
+        let index = |p: &Position| {
+            let (x, y) = (p.x - origin.x, p.y - origin.y);
+            if x < 0 || y < 0 || x >= self.width as isize || y >= self.height as isize {
+                None
+            } else {
+                Some((self.height - 1 - y as usize) * self.width + x as usize)
+            }
+        };
+        for p in trail {
+            if let Some(i) = index(p) {
+                cells[i] = Cell::Trail;
+            }
+        }
+        // knots nearer the head are drawn over those further back
+        for (k, p) in f.knots.iter().enumerate().rev() {
+            if let Some(i) = index(p) {
+                cells[i] = if k == 0 { Cell::Head } else { Cell::Knot };
+            }
+        }
+        cells
+    }
+
+    /// The frame as text, like the pictures in the puzzle
+    fn rows(&self, f: &Frame, origin: Position, trail: &HashSet<Position>) -> Vec<String> {
+        let mut grid: Vec<Vec<char>> = self
+            .cells(f, origin, trail)
+            .chunks(self.width)
+            .map(|row| {
+                row.iter()
+                    .map(|c| if *c == Cell::Trail { '#' } else { '.' })
+                    .collect()
+            })
+            .collect();
+        let mut put = |p: &Position, c: char| {
+            let (x, y) = (p.x - origin.x, p.y - origin.y);
+            if x >= 0 && y >= 0 && x < self.width as isize && y < self.height as isize {
+                grid[self.height - 1 - y as usize][x as usize] = c;
+            }
+        };
+        put(&Position::default(), 's');
+        let n = f.knots.len();
+        for (k, p) in f.knots.iter().enumerate().rev() {
+            let c = match k {
+                0 => 'H',
+                1 if n == 2 => 'T',
+                1..=9 => char::from(b'0' + k as u8),
+                _ => '*',
+            };
+            put(p, c);
+        }
+        grid.into_iter().map(|r| r.into_iter().collect()).collect()
+    }
+}
+
+/// Colour indices need two bits, but GIF wants at least two plus clear and end codes
+const MIN_CODE_SIZE: u8 = 2;
+
+/// GIF-flavoured LZW: variable-width codes packed least significant bit first
+fn lzw(data: &[u8]) -> Vec<u8> {
+    let clear: u16 = 1 << MIN_CODE_SIZE;
+    let end = clear + 1;
+
+    let mut out = vec![];
+    let (mut acc, mut bits) = (0u32, 0u32);
+    let mut emit = |code: u16, size: u32, out: &mut Vec<u8>| {
+        acc |= (code as u32) << bits;
+        bits += size;
+        while bits >= 8 {
+            out.push(acc as u8);
+            acc >>= 8;
+            bits -= 8;
+        }
+    };
+
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = end + 1;
+    let mut size = MIN_CODE_SIZE as u32 + 1;
+    emit(clear, size, &mut out);
+
+    let mut prefix: Option<u16> = None;
+    for &b in data {
+        let Some(p) = prefix else {
+            prefix = Some(b as u16);
+            continue;
+        };
+        if let Some(&code) = table.get(&(p, b)) {
+            prefix = Some(code);
+            continue;
+        }
+        emit(p, size, &mut out);
+        if next == 4096 {
+            // table's full: start again
+            emit(clear, size, &mut out);
+            table.clear();
+            next = end + 1;
+            size = MIN_CODE_SIZE as u32 + 1;
+        } else {
+            table.insert((p, b), next);
+            // the decoder is a code behind, so widen once this one no longer fits
+            if next == 1 << size {
+                size += 1;
+            }
+            next += 1;
+        }
+        prefix = Some(b as u16);
+    }
+    if let Some(p) = prefix {
+        emit(p, size, &mut out);
+    }
+    emit(end, size, &mut out);
+    if bits > 0 {
+        out.push(acc as u8);
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Straightforward GIF LZW decoder, to check the encoder against
+    fn unlzw(data: &[u8]) -> Vec<u8> {
+        let clear = 1usize << MIN_CODE_SIZE;
+        let end = clear + 1;
+        let (mut pos, mut size) = (0usize, MIN_CODE_SIZE as usize + 1);
+        let mut table: Vec<Vec<u8>> = vec![];
+        let mut prev: Option<usize> = None;
+        let mut out = vec![];
+        loop {
+            let mut code = 0;
+            for i in 0..size {
+                code |= ((data[(pos + i) / 8] >> ((pos + i) % 8)) as usize & 1) << i;
+            }
+            pos += size;
+            if code == clear {
+                table = (0..clear).map(|c| vec![c as u8]).collect();
+                table.extend([vec![], vec![]]);
+                size = MIN_CODE_SIZE as usize + 1;
+                prev = None;
+                continue;
+            }
+            if code == end {
+                return out;
+            }
+            let entry = if code < table.len() {
+                table[code].clone()
+            } else {
+                let p = &table[prev.unwrap()];
+                [p.clone(), vec![p[0]]].concat()
+            };
+            if let Some(p) = prev {
+                if table.len() < 4096 {
+                    table.push([table[p].clone(), vec![entry[0]]].concat());
+                }
+            }
+            if table.len() == 1 << size && size < 12 {
+                size += 1;
+            }
+            out.extend_from_slice(&entry);
+            prev = Some(code);
+        }
+    }
+
+    #[test]
+    fn lzw_round_trip() {
+        let mut seed = 7u32;
+        let noisy: Vec<u8> = (0..100_000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (seed >> 16) as u8 & 3
+            })
+            .collect();
+        for data in [vec![], vec![3], vec![0; 10_000], noisy] {
+            assert_eq!(unlzw(&lzw(&data)), data);
+        }
+    }
+
+    #[test]
+    fn viewport_follows_head() {
+        let view = Viewport {
+            width: 10,
+            height: 6,
+            margin: 2,
+        };
+        let head = |x, y| Position { x, y };
+        let o = view.follow(None, head(0, 0));
+        assert_eq!(o, head(-5, -3));
+        // still comfortably inside
+        assert_eq!(view.follow(Some(o), head(2, 0)), o);
+        // pushes the window along
+        assert_eq!(view.follow(Some(o), head(4, 0)), head(-3, -3));
+        assert_eq!(view.follow(Some(o), head(0, -2)), head(-5, -4));
+    }
+
+    #[test]
+    fn recording() {
+        let moves: Vec<Move> = ["R 4", "U 4", "L 3"]
+            .iter()
+            .map(|m| Move::parse(m).unwrap())
+            .collect();
+        let rec = Recording::record(&moves, 2);
+        assert_eq!(rec.frames.len(), 12);
+        assert_eq!(rec.first_detached(), None);
+
+        let view = Viewport {
+            width: 6,
+            height: 5,
+            margin: 0,
+        };
+        let text = rec.until(4).text(&view);
+        let lines: Vec<&str> = text.lines().collect();
+        // after R 4, with the window dragged along by the head
+        assert_eq!(lines[lines.len() - 7], "step 4, move 1, head at (4, 0)");
+        assert_eq!(
+            lines[lines.len() - 6..lines.len() - 1],
+            ["......", "......", ".s##TH", "......", "......"]
+        );
+
+        let cast = rec.cast(&view, 0.1);
+        assert_eq!(cast.lines().count(), 13);
+        assert!(cast
+            .lines()
+            .nth(1)
+            .unwrap()
+            .starts_with("[0.000, \"o\", \"\\u001b[H"));
+        assert!(cast.contains("\\r\\n"));
+
+        let gif = rec.gif(&view, 2, 10);
+        assert!(gif.starts_with(b"GIF89a"));
+        assert_eq!(gif.last(), Some(&0x3b));
+
+        let broken = Frame {
+            move_index: 0,
+            knots: vec![Position { x: 2, y: 0 }, Position::default()],
+        };
+        assert_eq!(broken.detached(), Some(1));
+    }
+}
@@ -0,0 +1,220 @@
+//! The rope itself: a head that moves and knots that follow
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+
+/// Convention: Right is +X, Up is +Y
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Position {
+    pub x: isize,
+    pub y: isize,
+}
+
+/// Straight from the docs
+impl std::ops::AddAssign for Position {
+    fn add_assign(&mut self, other: Self) {
+        *self = Self {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        };
+    }
+}
+
+impl std::fmt::Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
+/// Straight from the docs
+impl std::ops::Add for Position {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        }
+    }
+}
+
+impl std::ops::Sub for Position {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self {
+            x: self.x - other.x,
+            y: self.y - other.y,
+        }
+    }
+}
+
+impl Position {
+    /// One king's move in the direction of this vector
+    pub fn signum(self) -> Self {
+        Position {
+            x: self.x.signum(),
+            y: self.y.signum(),
+        }
+    }
+}
+
+/// Where the head goes, as a vector, and how many times over
+///
+/// The head travels there one king's move at a time: diagonally while
+/// both coordinates still need to change, then straight.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Move {
+    pub delta: Position,
+    pub count: usize,
+}
+
+impl Move {
+    /// Parse `R 4`, `UL 3` (any of `U`, `D`, `L`, `R` or a pair of them)
+    /// or a vector like `3,-2`, optionally followed by a repeat count
+    pub fn parse(line: &str) -> Result<Self> {
+        let (d, n) = line.trim().split_once(' ').unwrap_or((line.trim(), "1"));
+        let count: usize = n
+            .trim()
+            .parse()
+            .context(format!("Bad step count in {line:?}"))?;
+
+        let unit = if let Some((x, y)) = d.split_once(',') {
+            Position {
+                x: x.trim()
+                    .parse()
+                    .context(format!("Bad vector in {line:?}"))?,
+                y: y.trim()
+                    .parse()
+                    .context(format!("Bad vector in {line:?}"))?,
+            }
+        } else {
+            let mut unit = Position::default();
+            for c in d.chars() {
+                let (axis, sign) = match c {
+                    'U' => (&mut unit.y, 1),
+                    'D' => (&mut unit.y, -1),
+                    'L' => (&mut unit.x, -1),
+                    'R' => (&mut unit.x, 1),
+                    _ => bail!("Unknown move type in {line:?}"),
+                };
+                if *axis != 0 {
+                    bail!("Contradictory or repeated direction in {line:?}");
+                }
+                *axis = sign;
+            }
+            if d.is_empty() {
+                bail!("No direction in {line:?}");
+            }
+            unit
+        };
+
+        Ok(Move { delta: unit, count })
+    }
+
+    /// The king's moves the head makes, in order
+    pub fn steps(&self) -> impl Iterator<Item = Position> {
+        let delta = self.delta;
+        (0..self.count).flat_map(move |_| {
+            let mut remaining = delta;
+            std::iter::from_fn(move || {
+                let step = remaining.signum();
+                if step == Position::default() {
+                    None
+                } else {
+                    remaining = remaining - step;
+                    Some(step)
+                }
+            })
+        })
+    }
+}
+
+/// Implement the tail chase
+/// A knot touching (or on top of) the one ahead stays put;
+/// otherwise it takes one king's move straight towards it.
+/// This is the puzzle's rule for when the gap is two,
+/// and carries on sensibly for any bigger gap.
+pub fn catch_up(head: &Position, tail: &Position) -> Position {
+    let diff = *head - *tail;
+    if diff.x.abs() <= 1 && diff.y.abs() <= 1 {
+        *tail
+    } else {
+        *tail + diff.signum()
+    }
+}
+
+/// A rope of some number of knots, remembering everywhere each knot has been
+#[derive(Debug, Clone)]
+pub struct Rope {
+    knots: Vec<Position>,
+    /// How many times each knot has arrived in each cell, counting where it started
+    visits: Vec<HashMap<Position, usize>>,
+    /// The step each knot first left the origin on, counting from 1
+    first_moved: Vec<Option<usize>>,
+    steps: usize,
+}
+
+impl Rope {
+    /// All knots start at the origin; there are always at least two
+    pub fn new(knot_count: usize) -> Self {
+        let knots = vec![Position::default(); knot_count.max(2)];
+        let visits = knots.iter().map(|k| HashMap::from([(*k, 1)])).collect();
+        let first_moved = vec![None; knots.len()];
+        Rope {
+            knots,
+            visits,
+            first_moved,
+            steps: 0,
+        }
+    }
+
+    pub fn knots(&self) -> &[Position] {
+        &self.knots
+    }
+
+    /// Every cell knot `k` has been in (0 is the head), and how often it got there
+    pub fn visits(&self, k: usize) -> &HashMap<Position, usize> {
+        &self.visits[k]
+    }
+
+    pub fn tail_visits(&self) -> &HashMap<Position, usize> {
+        &self.visits[self.visits.len() - 1]
+    }
+
+    /// The head step on which knot `k` first moved, if it has
+    pub fn first_moved(&self, k: usize) -> Option<usize> {
+        self.first_moved[k]
+    }
+
+    /// How many single steps the head has taken
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    fn arrive(&mut self, k: usize, p: Position) {
+        self.knots[k] = p;
+        *self.visits[k].entry(p).or_default() += 1;
+        self.first_moved[k].get_or_insert(self.steps);
+    }
+
+    /// Move the head by one king's move and let the rest follow
+    pub fn step(&mut self, step: Position) {
+        self.steps += 1;
+        self.arrive(0, self.knots[0] + step);
+        // Iterate over remaining knots to propagate change
+        for k in 1..self.knots.len() {
+            let new = catch_up(&self.knots[k - 1], &self.knots[k]);
+            if new == self.knots[k] {
+                // this knot didn't move, so the others won't have to either
+                break;
+            }
+            self.arrive(k, new);
+        }
+    }
+
+    pub fn apply(&mut self, m: &Move) {
+        for s in m.steps() {
+            self.step(s);
+        }
+    }
+}
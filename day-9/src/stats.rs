@@ -0,0 +1,173 @@
+//! What each knot got up to over a whole run
+use crate::rope::{Position, Rope};
+use std::collections::HashMap;
+use std::fmt;
+
+/// The smallest rectangle holding every cell a knot visited
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Bounds {
+    pub min: Position,
+    pub max: Position,
+}
+
+impl Bounds {
+    pub fn of<'a>(cells: impl IntoIterator<Item = &'a Position>) -> Option<Self> {
+        let mut cells = cells.into_iter();
+        let first = *cells.next()?;
+        Some(cells.fold(
+            Bounds {
+                min: first,
+                max: first,
+            },
+            |b, p| Bounds {
+                min: Position {
+                    x: b.min.x.min(p.x),
+                    y: b.min.y.min(p.y),
+                },
+                max: Position {
+                    x: b.max.x.max(p.x),
+                    y: b.max.y.max(p.y),
+                },
+            },
+        ))
+    }
+
+    pub fn width(&self) -> usize {
+        (self.max.x - self.min.x) as usize + 1
+    }
+
+    pub fn height(&self) -> usize {
+        (self.max.y - self.min.y) as usize + 1
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnotStats {
+    /// 0 is the head
+    pub knot: usize,
+    /// Distinct cells visited
+    pub cells: usize,
+    /// Times it arrived anywhere, counting the start
+    pub arrivals: usize,
+    /// The cell it came back to most, and how often (ties go to the smallest cell)
+    pub busiest: (Position, usize),
+    pub bounds: Bounds,
+    /// The head step on which it first moved, if it ever did
+    pub first_moved: Option<usize>,
+}
+
+impl KnotStats {
+    pub fn of(rope: &Rope, knot: usize) -> Self {
+        let visits = rope.visits(knot);
+        let busiest = visits
+            .iter()
+            .map(|(p, n)| (*p, *n))
+            .max_by_key(|(p, n)| (*n, std::cmp::Reverse(*p)))
+            .unwrap_or_default();
+        KnotStats {
+            knot,
+            cells: visits.len(),
+            arrivals: visits.values().sum(),
+            busiest,
+            bounds: Bounds::of(visits.keys()).unwrap_or(Bounds {
+                min: busiest.0,
+                max: busiest.0,
+            }),
+            first_moved: rope.first_moved(knot),
+        }
+    }
+
+    /// One for every knot, head first
+    pub fn all(rope: &Rope) -> Vec<Self> {
+        (0..rope.knots().len()).map(|k| Self::of(rope, k)).collect()
+    }
+}
+
+impl fmt::Display for KnotStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "knot {}: {} cells, {} arrivals, busiest {} x{}, x {} to {}, y {} to {}, ",
+            self.knot,
+            self.cells,
+            self.arrivals,
+            self.busiest.0,
+            self.busiest.1,
+            self.bounds.min.x,
+            self.bounds.max.x,
+            self.bounds.min.y,
+            self.bounds.max.y
+        )?;
+        match self.first_moved {
+            Some(s) => write!(f, "first moved on step {s}"),
+            None => write!(f, "never moved"),
+        }
+    }
+}
+
+/// How often a knot arrived in each cell, north up
+///
+/// `.` is never, then `1` to `9` on a log scale up to the busiest cell;
+/// the start is marked `s` as in the other pictures.
+pub fn heatmap(visits: &HashMap<Position, usize>) -> String {
+    let Some(b) = Bounds::of(visits.keys()) else {
+        return String::new();
+    };
+    let top = (*visits.values().max().unwrap_or(&1) as f64).ln();
+    let mut out = String::with_capacity((b.width() + 1) * b.height());
+    for y in (b.min.y..=b.max.y).rev() {
+        for x in b.min.x..=b.max.x {
+            let p = Position { x, y };
+            out.push(match visits.get(&p) {
+                _ if p == Position::default() => 's',
+                None => '.',
+                Some(_) if top == 0.0 => '1',
+                Some(n) => char::from(b'1' + (8.0 * (*n as f64).ln() / top).round() as u8),
+            });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rope::Move;
+
+    #[test]
+    fn large_example() {
+        let mut rope = Rope::new(10);
+        for line in include_str!("../example_large.txt").lines() {
+            rope.apply(&Move::parse(line).unwrap());
+        }
+        let stats = KnotStats::all(&rope);
+        assert_eq!(stats.len(), 10);
+        assert_eq!(stats[9].cells, 36);
+        assert_eq!(stats[0].first_moved, Some(1));
+        // the tail sits out R 5 and U 8 and only gets going partway through L 8
+        assert_eq!(stats[9].first_moved, Some(5 + 8 + 4));
+        assert_eq!(stats[0].arrivals, rope.steps() + 1);
+        // the tail cuts the corners, so it never gets as far out as the head
+        assert_eq!(stats[0].bounds.width(), 26);
+        assert_eq!(stats[9].bounds.width(), 22);
+        assert!(stats.windows(2).all(|w| w[1].cells <= w[0].cells));
+
+        let map = heatmap(rope.tail_visits());
+        assert_eq!(map.lines().count(), stats[9].bounds.height());
+        assert_eq!(map.chars().filter(|c| c.is_ascii_digit()).count(), 35);
+        assert!(map.contains('s'));
+    }
+
+    #[test]
+    fn still_rope() {
+        let rope = Rope::new(3);
+        let s = KnotStats::of(&rope, 2);
+        assert_eq!((s.cells, s.arrivals, s.first_moved), (1, 1, None));
+        assert_eq!(
+            s.to_string(),
+            "knot 2: 1 cells, 1 arrivals, busiest (0, 0) x1, x 0 to 0, y 0 to 0, never moved"
+        );
+        assert_eq!(heatmap(rope.visits(0)), "s\n");
+    }
+}